@@ -0,0 +1,194 @@
+use core::fmt::Display;
+
+use alloc::vec::Vec;
+
+use super::{GptLayout, Header, Partition, Uuid};
+
+/// A partition slot (one-based) that differs between two layouts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PartitionChange {
+    /// The slot is only used in the new layout
+    Added(usize, Partition),
+    /// The slot is only used in the old layout
+    Removed(usize, Partition),
+    /// The slot is used in both but the entries differ
+    Modified {
+        index: usize,
+        old: Partition,
+        new: Partition,
+    },
+}
+
+/// What [`GptLayout::diff`] found, empty if the layouts describe the same disk.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayoutDiff {
+    /// The changed slots in ascending order
+    pub partitions: Vec<PartitionChange>,
+    /// The old and new disk GUID if it changed
+    pub disk_guid: Option<(Uuid, Uuid)>,
+    /// The old and new header if anything besides the disk GUID, the location and the
+    /// CRCs changed, see [`Header::content_eq`]
+    pub geometry: Option<(Header, Header)>,
+}
+
+impl LayoutDiff {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.partitions.is_empty() && self.disk_guid.is_none() && self.geometry.is_none()
+    }
+}
+
+impl Display for LayoutDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if let Some((old, new)) = &self.disk_guid {
+            writeln!(f, "disk guid: {old} -> {new}")?;
+        }
+        if let Some((old, new)) = &self.geometry {
+            writeln!(
+                f,
+                "geometry: {} entries of {} bytes, usable {}..={} -> {} entries of {} bytes, usable {}..={}",
+                old.num_parts,
+                old.part_size,
+                old.first_usable,
+                old.last_usable,
+                new.num_parts,
+                new.part_size,
+                new.first_usable,
+                new.last_usable
+            )?;
+        }
+        for change in self.partitions.iter() {
+            match change {
+                PartitionChange::Added(index, part) => writeln!(
+                    f,
+                    "+ {index}: {} ({}..={})",
+                    part.describe(),
+                    part.start_lba,
+                    part.end_lba
+                )?,
+                PartitionChange::Removed(index, part) => writeln!(
+                    f,
+                    "- {index}: {} ({}..={})",
+                    part.describe(),
+                    part.start_lba,
+                    part.end_lba
+                )?,
+                PartitionChange::Modified { index, old, new } => writeln!(
+                    f,
+                    "~ {index}: {} ({}..={}) -> {} ({}..={})",
+                    old.describe(),
+                    old.start_lba,
+                    old.end_lba,
+                    new.describe(),
+                    new.start_lba,
+                    new.end_lba
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GptLayout {
+    /// What changed from this layout to `other`, comparing the primary headers and the
+    /// primary partition arrays slot by slot. `None` if either layout has no primary header.
+    pub fn diff(&self, other: &GptLayout) -> Option<LayoutDiff> {
+        let count = self.slots.len().max(other.slots.len());
+        Some(diff_headers(
+            self.primary_header.as_deref()?,
+            other.primary_header.as_deref()?,
+            diff_slots(
+                count,
+                |index| self.partition(index),
+                |index| other.partition(index),
+            ),
+        ))
+    }
+
+    /// Where the backup copy disagrees with the primary one, the primary being the old
+    /// side. The headers' locations differ by design and are not reported. `None` if
+    /// either header is missing.
+    pub fn backup_diff(&self) -> Option<LayoutDiff> {
+        let count = self.slots.len().max(self.backup_slots.len());
+        Some(diff_headers(
+            self.primary_header.as_deref()?,
+            self.backup_header.as_deref()?,
+            diff_slots(
+                count,
+                |index| self.partition(index),
+                |index| self.backup_partition(index),
+            ),
+        ))
+    }
+}
+
+fn diff_headers(old: &Header, new: &Header, partitions: Vec<PartitionChange>) -> LayoutDiff {
+    let same_geometry = Header {
+        disk_guid: new.disk_guid,
+        ..old.clone()
+    }
+    .content_eq(new);
+    LayoutDiff {
+        partitions,
+        disk_guid: (old.disk_guid != new.disk_guid).then_some((old.disk_guid, new.disk_guid)),
+        geometry: (!same_geometry).then(|| (old.clone(), new.clone())),
+    }
+}
+
+/// Compare slots `1..count` of two partition arrays.
+fn diff_slots<'a, 'b>(
+    count: usize,
+    old: impl Fn(usize) -> Option<&'a Partition>,
+    new: impl Fn(usize) -> Option<&'b Partition>,
+) -> Vec<PartitionChange> {
+    (1..count)
+        .filter_map(|index| match (old(index), new(index)) {
+            (None, Some(new)) => Some(PartitionChange::Added(index, new.clone())),
+            (Some(old), None) => Some(PartitionChange::Removed(index, old.clone())),
+            (Some(old), Some(new)) if old != new => Some(PartitionChange::Modified {
+                index,
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn missing_headers_have_no_diff() {
+        let empty = GptLayout::new();
+        assert!(empty.diff(&testing::layout()).is_none());
+        assert!(testing::layout().diff(&empty).is_none());
+        assert!(empty.backup_diff().is_none());
+        assert!(testing::layout()
+            .diff(&testing::layout())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn backup_diff_reports_a_mismatched_backup() {
+        let mut layout = testing::layout();
+        assert!(layout.backup_diff().unwrap().is_empty());
+        let old = layout.partition(2).unwrap().clone();
+        layout.partition_mut(2).unwrap().end_lba = 5000;
+        let new = layout.partition(2).unwrap().clone();
+        let diff = layout.backup_diff().unwrap();
+        // the primary is the old side
+        assert_eq!(
+            diff.partitions,
+            [PartitionChange::Modified {
+                index: 2,
+                old: new,
+                new: old,
+            }]
+        );
+        assert!(diff.disk_guid.is_none() && diff.geometry.is_none());
+    }
+}
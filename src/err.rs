@@ -7,6 +7,8 @@ pub enum HeaderError {
     /// Invalid GPT Signature
     /// This means your trying to read a gpt header which does not exist or is invalid.
     InvalidGptSignature,
+    /// The provided buffer is shorter than the 92 bytes of a header
+    InvalidHeaderLength,
     /// Invalid CRC32 Checksum
     ///
     /// This means the header was corrupted or not fully written.
@@ -23,6 +25,24 @@ pub enum HeaderError {
     WritingToWrongLba,
     /// The Disk is to small to hold a backup header
     ToSmallForBackup,
+    /// header_size is smaller than 92 bytes or larger than a sector
+    InvalidHeaderSize,
+    /// part_size isn't a non zero multiple of 128
+    InvalidPartitionEntrySize,
+    // Geometry errors
+    /// my_lba isn't where this header is supposed to live (LBA 1 for the primary, the last
+    /// LBA for the backup)
+    InvalidMyLba,
+    /// backup_lba doesn't point at the other header
+    InvalidBackupLba,
+    /// part_start doesn't lie between the header and the usable range
+    InvalidPartitionArrayLba,
+    /// first_usable and last_usable don't leave room for both partition arrays
+    InvalidUsableRange,
+    /// The disk GUID is nil, generate one with `Uuid::new_v4`
+    NilDiskGuid,
+    /// The block size is zero or not a power of two
+    InvalidBlockSize,
 }
 
 impl fmt::Display for HeaderError {
@@ -30,6 +50,7 @@ impl fmt::Display for HeaderError {
         use HeaderError::*;
         let desc = match self {
             InvalidGptSignature => "Invalid GPT Signature, the header does not exist or is invalid",
+            InvalidHeaderLength => "The provided buffer is shorter than a gpt header",
             InvalidCRC32Checksum => "CRC32 Checksum Mismatch, the header is corrupted",
             MissingBackupLba => "HeaderBuilder expects the field backup_lba to be set",
             BackupLbaToEarly => {
@@ -38,14 +59,26 @@ impl fmt::Display for HeaderError {
             WritingToWrongLba => {
                 "you trying to write to the wrong lba (example calling write_primary instead of write_backup)"
             },
-            ToSmallForBackup => "the disk is to small to hold a backup header"
+            ToSmallForBackup => "the disk is to small to hold a backup header",
+            InvalidHeaderSize => "header_size must be between 92 bytes and the sector size",
+            InvalidPartitionEntrySize => "part_size must be a non zero multiple of 128",
+            InvalidMyLba => "my_lba doesn't match where the header is supposed to be",
+            InvalidBackupLba => "backup_lba doesn't point at the other header",
+            InvalidPartitionArrayLba => {
+                "part_start doesn't lie between the header and the usable range"
+            },
+            InvalidUsableRange => {
+                "first_usable and last_usable don't leave room for both partition arrays"
+            },
+            NilDiskGuid => "the disk GUID must not be nil",
+            InvalidBlockSize => "the block size must be a non zero power of two",
         };
         write!(fmt, "{desc}")
     }
 }
 
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 /// Errors returned when interacting with a Gpt Disk.
 pub enum MBRError {
     /// The provided buffer does not match the expected mbr length
@@ -54,6 +87,14 @@ pub enum MBRError {
     InvalidMBRSignature,
     /// Invalid Partition Length != 16
     InvalidPartitionLength,
+    /// The records don't form a protective MBR (one 0xEE record spanning the disk)
+    InvalidProtectiveLayout,
+    /// A hybrid MBR can only mirror up to three GPT partitions
+    TooManyHybridRecords,
+    /// The record marked active is not one of the generated records
+    InvalidActiveRecord,
+    /// The partition doesn't fit in the 32-bit LBA range of an MBR record
+    HybridOutOfRange,
 }
 
 impl fmt::Display for MBRError {
@@ -63,7 +104,356 @@ impl fmt::Display for MBRError {
             InvalidMBRLength => "The provided buffer does not match the expected mbr length",
             InvalidMBRSignature => "Invalid MBR signature",
             InvalidPartitionLength => "Invalid Partition length expected 16",
+            InvalidProtectiveLayout => {
+                "The MBR is not protective, expected a single 0xEE record spanning the disk"
+            }
+            TooManyHybridRecords => "A hybrid MBR can only mirror up to three partitions",
+            InvalidActiveRecord => "The record marked active is not one of the generated records",
+            HybridOutOfRange => "The partition doesn't fit in the 32-bit LBA range of an MBR",
+        };
+        write!(fmt, "{desc}")
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Errors returned when interacting with a partition entry.
+pub enum PartitionError {
+    /// The provided buffer is shorter than a partition entry
+    InvalidEntryLength,
+    /// The name doesn't fit in the 36 UTF-16 code units of an entry
+    NameTooLong,
+    /// The partition would end before it starts
+    InvalidRange,
+    /// A partition needs a type, a nil type GUID marks an unused entry
+    MissingTypeGuid,
+    /// The header giving the entry size of the partition array isn't initialized
+    MissingHeader,
+}
+
+impl fmt::Display for PartitionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PartitionError::*;
+        let desc = match self {
+            InvalidEntryLength => "The provided buffer is shorter than a partition entry",
+            NameTooLong => "The name doesn't fit in the 36 UTF-16 code units of an entry",
+            InvalidRange => "The partition would end before it starts",
+            MissingTypeGuid => "The partition has a nil type GUID",
+            MissingHeader => "The header of the partition array is not initialized",
+        };
+        write!(fmt, "{desc}")
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Errors returned when parsing a GUID from a string.
+pub enum UuidError {
+    /// The string isn't 36 characters long or the hyphens are misplaced
+    InvalidLength,
+    /// One of the characters isn't a hexadecimal digit
+    InvalidCharacter,
+}
+
+impl fmt::Display for UuidError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use UuidError::*;
+        let desc = match self {
+            InvalidLength => "expected a GUID of the form xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx",
+            InvalidCharacter => "the GUID contains a character that isn't a hexadecimal digit",
+        };
+        write!(fmt, "{desc}")
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Errors returned when a partition name isn't valid UTF-16.
+pub enum PartitionNameError {
+    /// A surrogate code unit without its other half
+    UnpairedSurrogate(u16),
+}
+
+impl fmt::Display for PartitionNameError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PartitionNameError::*;
+        match self {
+            UnpairedSurrogate(unit) => {
+                write!(fmt, "the name contains the unpaired surrogate {unit:#06x}")
+            }
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Errors returned when the headers, tables and disk of a GptLayout don't fit together.
+pub enum LayoutError {
+    /// The disk is to small to hold the structures the headers describe
+    DiskTooSmall,
+    /// The backup header fails its CRC32 check so it can't be used for recovery
+    NoValidBackup,
+    /// The two partitions (one-based indices) have the same part_guid
+    DuplicatePartitionGuid(usize, usize),
+    /// Every slot of the partition array is taken
+    PartitionTableFull,
+    /// The protective MBR doesn't protect the disk
+    InvalidProtectiveMbr(MBRError),
+    /// The header is misplaced or its geometry is inconsistent
+    InvalidHeader {
+        header: HeaderKind,
+        error: HeaderError,
+    },
+    /// The header's crc32 doesn't match its content
+    HeaderCrcMismatch(HeaderKind),
+    /// The crc32_parts of the header doesn't match its partition array
+    PartitionTableCrcMismatch(HeaderKind),
+    /// The primary and backup headers describe different disks
+    HeadersMismatch,
+    /// The two partitions (one-based indices) share LBAs
+    PartitionsOverlap(usize, usize),
+    /// The partition (one-based index) reaches outside the usable range
+    PartitionOutOfBounds { index: usize, start: u64, end: u64 },
+    /// There is no partition in the slot (one-based index)
+    PartitionNotFound(usize),
+    /// The partition (one-based index) would end before it starts
+    InvalidPartitionRange(usize),
+    /// Writing the layout out and reading it back gives a different layout
+    RoundTripMismatch,
+    /// The partition (one-based index) overlaps the MBR, a header or a partition array
+    OverlapsMetadata(usize),
+    /// The partition has a nil type GUID, which marks an unused entry
+    MissingTypeGuid,
+    /// The disk image is larger than what can be buffered in memory
+    ImageTooLarge,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LayoutError::*;
+        match self {
+            DiskTooSmall => write!(
+                fmt,
+                "the disk is to small to hold the structures the headers describe"
+            ),
+            NoValidBackup => write!(
+                fmt,
+                "the backup header is corrupted, it can't be used for recovery"
+            ),
+            DuplicatePartitionGuid(first, second) => write!(
+                fmt,
+                "partitions {first} and {second} share the same partition GUID"
+            ),
+            PartitionTableFull => write!(fmt, "every slot of the partition array is taken"),
+            InvalidProtectiveMbr(err) => write!(fmt, "invalid protective MBR: {err}"),
+            InvalidHeader { header, error } => write!(fmt, "invalid {header} header: {error}"),
+            HeaderCrcMismatch(header) => write!(fmt, "{header} header CRC32 mismatch"),
+            PartitionTableCrcMismatch(header) => {
+                write!(fmt, "{header} partition table CRC32 mismatch")
+            }
+            HeadersMismatch => write!(fmt, "primary and backup headers disagree"),
+            PartitionsOverlap(first, second) => {
+                write!(fmt, "partitions {first} and {second} overlap")
+            }
+            PartitionOutOfBounds { index, start, end } => write!(
+                fmt,
+                "partition {index} ({start}..={end}) reaches outside the usable range"
+            ),
+            PartitionNotFound(index) => write!(fmt, "there is no partition in slot {index}"),
+            InvalidPartitionRange(index) => {
+                write!(fmt, "partition {index} would end before it starts")
+            }
+            OverlapsMetadata(index) => {
+                write!(fmt, "partition {index} overlaps the GPT metadata")
+            }
+            RoundTripMismatch => write!(
+                fmt,
+                "the layout reads back differently from what was written"
+            ),
+            MissingTypeGuid => write!(fmt, "the partition has a nil type GUID"),
+            ImageTooLarge => write!(fmt, "the disk image is to large to buffer in memory"),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Errors returned when reading from or writing to the underlying disk.
+pub enum BlockError {
+    /// The source ended before all the expected bytes were read
+    UnexpectedEof,
+    /// The requested blocks lie past the end of the disk
+    OutOfRange,
+    /// The underlying reader or device failed
+    Device,
+    /// The block size is zero
+    InvalidBlockSize,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BlockError::*;
+        let desc = match self {
+            UnexpectedEof => "the source ended before all the expected bytes were read",
+            OutOfRange => "the requested blocks lie past the end of the disk",
+            Device => "the underlying reader or device failed",
+            InvalidBlockSize => "the block size must not be zero",
         };
         write!(fmt, "{desc}")
     }
 }
+
+impl core::error::Error for HeaderError {}
+
+impl core::error::Error for MBRError {}
+
+impl core::error::Error for PartitionError {}
+
+impl core::error::Error for UuidError {}
+
+impl core::error::Error for PartitionNameError {}
+
+impl core::error::Error for LayoutError {}
+
+impl core::error::Error for BlockError {}
+
+#[non_exhaustive]
+#[derive(Debug)]
+/// Errors returned by the operations working on a whole Gpt Disk.
+pub enum GptError {
+    /// The protective MBR is invalid
+    Mbr(MBRError),
+    /// One of the GPT headers is invalid
+    Header(HeaderError),
+    /// A partition entry is invalid
+    Partition(PartitionError),
+    /// The layout as a whole is inconsistent
+    Layout(LayoutError),
+    /// Reading or writing the disk failed
+    Block(BlockError),
+}
+
+impl fmt::Display for GptError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GptError::Mbr(err) => write!(fmt, "MBR error: {err}"),
+            GptError::Header(err) => write!(fmt, "Header error: {err}"),
+            GptError::Partition(err) => write!(fmt, "Partition error: {err}"),
+            GptError::Layout(err) => write!(fmt, "Layout error: {err}"),
+            GptError::Block(err) => write!(fmt, "Block error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for GptError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            GptError::Mbr(err) => Some(err),
+            GptError::Header(err) => Some(err),
+            GptError::Partition(err) => Some(err),
+            GptError::Layout(err) => Some(err),
+            GptError::Block(err) => Some(err),
+        }
+    }
+}
+
+impl From<MBRError> for GptError {
+    fn from(err: MBRError) -> Self {
+        GptError::Mbr(err)
+    }
+}
+
+impl From<HeaderError> for GptError {
+    fn from(err: HeaderError) -> Self {
+        GptError::Header(err)
+    }
+}
+
+impl From<PartitionError> for GptError {
+    fn from(err: PartitionError) -> Self {
+        GptError::Partition(err)
+    }
+}
+
+impl From<LayoutError> for GptError {
+    fn from(err: LayoutError) -> Self {
+        GptError::Layout(err)
+    }
+}
+
+impl From<BlockError> for GptError {
+    fn from(err: BlockError) -> Self {
+        GptError::Block(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which of the two GPT headers something refers to.
+pub enum HeaderKind {
+    /// The header at LBA 1
+    Primary,
+    /// The header at the last LBA
+    Backup,
+}
+
+impl fmt::Display for HeaderKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderKind::Primary => write!(fmt, "primary"),
+            HeaderKind::Backup => write!(fmt, "backup"),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy)]
+/// Non fatal problems found while leniently parsing a disk.
+pub enum GptWarning {
+    /// The protective MBR is missing or doesn't protect the disk
+    InvalidProtectiveMbr(MBRError),
+    /// The header can't be read, it got rebuilt from the other one
+    InvalidHeader {
+        header: HeaderKind,
+        error: HeaderError,
+    },
+    /// The header's crc32 doesn't match its content
+    HeaderCrcMismatch(HeaderKind),
+    /// The crc32_parts of the header doesn't match its partition array
+    PartitionTableCrcMismatch(HeaderKind),
+    /// The primary and backup headers describe different disks
+    HeadersMismatch,
+    /// The entry (one-based index) has a nil type GUID but leftover data
+    GhostEntry(usize),
+    /// The partition (one-based index) doesn't start on a 1 MiB boundary
+    MisalignedPartition(usize),
+    /// The partition (one-based index) reaches outside the usable range
+    PartitionOutOfBounds(usize),
+    /// The header's entries aren't the usual 128 bytes
+    NonStandardEntrySize { header: HeaderKind, part_size: u32 },
+}
+
+impl fmt::Display for GptWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use GptWarning::*;
+        match self {
+            InvalidProtectiveMbr(err) => write!(fmt, "invalid protective MBR: {err}"),
+            InvalidHeader { header, error } => write!(fmt, "invalid {header} header: {error}"),
+            HeaderCrcMismatch(header) => write!(fmt, "{header} header CRC32 mismatch"),
+            PartitionTableCrcMismatch(header) => {
+                write!(fmt, "{header} partition table CRC32 mismatch")
+            }
+            HeadersMismatch => write!(fmt, "primary and backup headers disagree"),
+            GhostEntry(index) => write!(fmt, "entry {index} is deleted but not zeroed"),
+            MisalignedPartition(index) => {
+                write!(fmt, "partition {index} isn't aligned to 1 MiB")
+            }
+            PartitionOutOfBounds(index) => {
+                write!(fmt, "partition {index} reaches outside the usable range")
+            }
+            NonStandardEntrySize { header, part_size } => write!(
+                fmt,
+                "{header} header uses {part_size} byte entries instead of 128"
+            ),
+        }
+    }
+}
@@ -1,16 +1,53 @@
 use core::fmt::Display;
 
-use super::{copy_bytes, write_to_bytes, HeaderError, Uuid};
+use super::{
+    copy_bytes, crc, write_to_bytes, HeaderError, Uuid, MIN_PARTITION_NUM, PARTITION_LBA_SIZE,
+};
+use alloc::vec;
 use byteorder::{ByteOrder, LittleEndian};
 pub const PRIMARY_HEADER_LBA: usize = 1;
+/// The GPT signature as characters, headers hold and compare [`GPT_SIGNATURE_BYTES`].
+#[deprecated(note = "use GPT_SIGNATURE_BYTES, the signature as it appears on disk")]
 pub const GPT_SIGNATURE: [char; 8] = ['E', 'F', 'I', ' ', 'P', 'A', 'R', 'T'];
+/// The signature "EFI PART" as it appears on disk.
+pub const GPT_SIGNATURE_BYTES: [u8; 8] = *b"EFI PART";
+/// Size in bytes of the fields the spec defines, what `serialize` produces.
+pub const HEADER_SIZE: usize = 92;
+/// The largest block size in use, and so the largest `header_size` a header can have.
+const MAX_BLOCK_SIZE: usize = 4096;
+/// A GPT revision, major then minor, stored on disk as two little-endian `u16` with the
+/// minor first.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Revision(pub u16, pub u16);
+
+impl Revision {
+    /// Revision 1.0, the only one the spec defines so far.
+    pub const V1_0: Revision = Revision(1, 0);
+
+    pub fn major(&self) -> u16 {
+        self.0
+    }
+
+    pub fn minor(&self) -> u16 {
+        self.1
+    }
+}
+
+impl Display for Revision {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}", self.0, self.1)
+    }
+}
+
 /// Header describing a GPT disk.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// GPT header magic signature, hardcoded to "EFI PART".
-    pub signature: [char; 8], // Offset  0. "EFI PART", 45h 46h 49h 20h 50h 41h 52h 54h
+    pub signature: [u8; 8], // Offset  0. "EFI PART", 45h 46h 49h 20h 50h 41h 52h 54h
     /// major, minor
-    pub revision: (u16, u16), // Offset  8
+    pub revision: Revision, // Offset  8
     /// little endian
     pub header_size: u32, // Offset 12
     /// CRC32 of the header, will be incorrect after changing something until the
@@ -40,15 +77,22 @@ pub struct Header {
 }
 
 impl Header {
+    /// Parse a header from its block, `header_size` must fit in `blk` and `part_size` must
+    /// be a multiple of 128.
     pub fn deserialize(blk: &[u8]) -> Result<Self, HeaderError> {
-        let _ = check_signature(&blk[0..8])?;
+        if blk.len() < HEADER_SIZE {
+            return Err(HeaderError::InvalidHeaderLength);
+        }
+        check_signature(&blk[0..8])?;
+        let mut signature = [0u8; 8];
+        signature.copy_from_slice(&blk[0..8]);
         let crc32 = LittleEndian::read_u32(&blk[16..20]);
         let header = Self {
-            signature: GPT_SIGNATURE,
+            signature,
             revision: {
                 let minor = LittleEndian::read_u16(&blk[8..10]);
                 let major = LittleEndian::read_u16(&blk[10..12]);
-                (major, minor)
+                Revision(major, minor)
             },
             header_size: LittleEndian::read_u32(&blk[12..16]),
             crc32,
@@ -63,22 +107,26 @@ impl Header {
             part_size: LittleEndian::read_u32(&blk[84..88]),
             crc32_parts: LittleEndian::read_u32(&blk[88..92]),
         };
+        if !header.is_supported_revision() {
+            log::warn!("unexpected GPT revision {}", header.revision);
+        }
+        header.validate_header_size(blk.len())?;
+        check_part_size(header.part_size)?;
         Ok(header)
     }
 
-    pub fn serialize(&self) -> [u8; size_of::<Self>()] {
-        let mut bytes = [0u8; size_of::<Self>()];
-        for (index, ele) in self.signature.iter().enumerate() {
-            bytes[index] = *ele as u8;
-        }
+    pub fn serialize(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&self.signature);
         write_to_bytes::<2>(self.revision.1 as u64, &mut bytes, 8);
         write_to_bytes::<2>(self.revision.0 as u64, &mut bytes, 10);
         write_to_bytes::<4>(self.header_size as u64, &mut bytes, 12);
         write_to_bytes::<4>(self.crc32 as u64, &mut bytes, 16);
+        write_to_bytes::<4>(self.reserved as u64, &mut bytes, 20);
         write_to_bytes::<8>(self.my_lba, &mut bytes, 24);
         write_to_bytes::<8>(self.backup_lba, &mut bytes, 32);
-        write_to_bytes::<8>(self.first_usable as u64, &mut bytes, 40);
-        write_to_bytes::<8>(self.last_usable as u64, &mut bytes, 48);
+        write_to_bytes::<8>(self.first_usable, &mut bytes, 40);
+        write_to_bytes::<8>(self.last_usable, &mut bytes, 48);
         copy_bytes(&self.disk_guid, &mut bytes, 56, 16);
         write_to_bytes::<8>(self.part_start, &mut bytes, 72);
         write_to_bytes::<4>(self.num_parts as u64, &mut bytes, 80);
@@ -88,46 +136,645 @@ impl Header {
     }
 }
 
-fn check_signature(sig: &[u8]) -> Result<(), HeaderError> {
-    for (index, ele) in sig.iter().enumerate() {
-        let item = *ele as char;
-        if !item.eq(&GPT_SIGNATURE[index]) {
-            return Err(HeaderError::InvalidGptSignature);
+impl Header {
+    /// Compute the CRC32 of the first `header_size` bytes of the header with the `crc32`
+    /// field zeroed, as the spec defines it. Past the 92 bytes of fields the header is zero
+    /// up to `header_size`, which is capped at the largest block size, 4096 bytes.
+    pub fn calculate_crc32(&self) -> u32 {
+        let mut bytes = vec![0u8; (self.header_size as usize).clamp(HEADER_SIZE, MAX_BLOCK_SIZE)];
+        bytes[..HEADER_SIZE].copy_from_slice(&self.serialize());
+        bytes[16..20].fill(0);
+        let len = (self.header_size as usize).min(bytes.len());
+        crc::crc32(&bytes[..len])
+    }
+
+    /// Whether both headers describe the same disk geometry, ignoring the fields that
+    /// legitimately differ between a primary and a backup header: the CRCs, `my_lba`,
+    /// `backup_lba` and `part_start`.
+    pub fn content_eq(&self, other: &Header) -> bool {
+        self.disk_guid == other.disk_guid
+            && self.num_parts == other.num_parts
+            && self.part_size == other.part_size
+            && self.first_usable == other.first_usable
+            && self.last_usable == other.last_usable
+            && self.revision == other.revision
+            && self.header_size == other.header_size
+    }
+
+    /// Number of blocks of `block_size` bytes the partition array takes, e.g. 32 for 128
+    /// entries of 128 bytes on 512 byte blocks.
+    pub fn table_sectors(&self, block_size: u64) -> u64 {
+        table_sectors(self.num_parts, self.part_size, block_size)
+    }
+
+    /// Whether `signature` holds "EFI PART", the check `deserialize` starts with.
+    pub fn signature_valid(&self) -> bool {
+        check_signature(&self.signature).is_ok()
+    }
+
+    /// Whether the header is revision 1.0, the only one this crate knows how to read.
+    pub fn is_supported_revision(&self) -> bool {
+        self.revision == Revision::V1_0
+    }
+
+    /// Whether the stored `crc32` matches the header content.
+    pub fn crc32_valid(&self) -> bool {
+        self.crc32 == self.calculate_crc32()
+    }
+
+    /// Check `header_size` covers at least the 92 bytes of defined fields and doesn't spill
+    /// out of the header's sector, which would make the CRC32 computation read garbage.
+    pub fn validate_header_size(&self, sector_size: usize) -> Result<(), HeaderError> {
+        if self.header_size < HEADER_SIZE as u32 || self.header_size as usize > sector_size {
+            return Err(HeaderError::InvalidHeaderSize);
         }
+        Ok(())
+    }
+
+    /// A primary header for a disk of `disk_lba_count` LBAs with a partition array of
+    /// `num_parts` entries of `part_size` bytes. The array starts at LBA 2, the usable range
+    /// lies between it and the backup array, which sits right before the backup header at
+    /// the last LBA. `disk_guid` is nil and the CRCs are zero, fill in and recompute.
+    /// `part_size` must be a non zero multiple of 128 like `deserialize` expects and
+    /// `block_size` a power of two.
+    pub fn with_geometry(
+        disk_lba_count: u64,
+        num_parts: u32,
+        part_size: u32,
+        block_size: u64,
+    ) -> Result<Self, HeaderError> {
+        check_part_size(part_size)?;
+        if !block_size.is_power_of_two() {
+            return Err(HeaderError::InvalidBlockSize);
+        }
+        let table = table_sectors(num_parts, part_size, block_size);
+        let backup_lba = disk_lba_count
+            .checked_sub(1)
+            .ok_or(HeaderError::ToSmallForBackup)?;
+        let first_usable = PRIMARY_HEADER_LBA as u64 + 1 + table;
+        let last_usable = backup_lba
+            .checked_sub(table + 1)
+            .filter(|last| *last >= first_usable)
+            .ok_or(HeaderError::ToSmallForBackup)?;
+        Ok(Self {
+            signature: GPT_SIGNATURE_BYTES,
+            revision: Revision::V1_0,
+            header_size: HEADER_SIZE as u32,
+            crc32: 0,
+            reserved: 0,
+            my_lba: PRIMARY_HEADER_LBA as u64,
+            backup_lba,
+            first_usable,
+            last_usable,
+            disk_guid: Uuid::default(),
+            part_start: PRIMARY_HEADER_LBA as u64 + 1,
+            num_parts,
+            part_size,
+            crc32_parts: 0,
+        })
+    }
+
+    /// A fresh primary header for a disk of `disk_lba_count` LBAs identified by `disk_guid`,
+    /// with the standard array of 128 entries of 128 bytes. The CRCs are zero, recompute
+    /// them once the partitions are in place. `disk_guid` must not be nil.
+    pub fn new_primary(
+        disk_guid: Uuid,
+        disk_lba_count: u64,
+        block_size: u64,
+    ) -> Result<Self, HeaderError> {
+        if disk_guid.is_nil() {
+            return Err(HeaderError::NilDiskGuid);
+        }
+        let header = Self::with_geometry(
+            disk_lba_count,
+            MIN_PARTITION_NUM as u32,
+            PARTITION_LBA_SIZE as u32,
+            block_size,
+        )?;
+        Ok(Self {
+            disk_guid,
+            ..header
+        })
+    }
+
+    /// The backup counterpart of this primary header on a disk of `disk_lba_count` LBAs:
+    /// at the last LBA pointing back at LBA 1, with its partition array right in front of
+    /// it. The geometry and `disk_guid` are kept, `crc32` is cleared for later recompute.
+    pub fn as_backup(&self, disk_lba_count: u64, block_size: u64) -> Header {
+        let layout = backup_layout(disk_lba_count, self.num_parts, self.part_size, block_size);
+        Header {
+            crc32: 0,
+            my_lba: layout.backup_header_lba,
+            backup_lba: PRIMARY_HEADER_LBA as u64,
+            part_start: layout.backup_table_start_lba,
+            ..self.clone()
+        }
+    }
+
+    /// Check the header is a well placed primary header for a disk of `disk_lba_count`
+    /// LBAs: at LBA 1, pointing at the last LBA, with its partition array between itself and
+    /// `first_usable` and room left for the backup array after `last_usable`.
+    pub fn validate_primary(&self, disk_lba_count: u64) -> Result<(), HeaderError> {
+        let last_lba = disk_lba_count.saturating_sub(1);
+        if self.my_lba != PRIMARY_HEADER_LBA as u64 {
+            return Err(HeaderError::InvalidMyLba);
+        }
+        if self.backup_lba != last_lba {
+            return Err(HeaderError::InvalidBackupLba);
+        }
+        if self.part_start <= self.my_lba || self.part_start >= self.first_usable {
+            return Err(HeaderError::InvalidPartitionArrayLba);
+        }
+        let after_usable = self
+            .last_usable
+            .checked_add(1)
+            .ok_or(HeaderError::InvalidUsableRange)?;
+        if self.first_usable > self.last_usable || after_usable >= last_lba {
+            return Err(HeaderError::InvalidUsableRange);
+        }
+        Ok(())
+    }
+
+    /// Check the header is a well placed backup header for a disk of `disk_lba_count` LBAs:
+    /// at the last LBA, pointing at LBA 1, with its partition array between `last_usable`
+    /// and itself and room left for the primary array before `first_usable`.
+    pub fn validate_backup(&self, disk_lba_count: u64) -> Result<(), HeaderError> {
+        let last_lba = disk_lba_count.saturating_sub(1);
+        if self.my_lba != last_lba {
+            return Err(HeaderError::InvalidMyLba);
+        }
+        if self.backup_lba != PRIMARY_HEADER_LBA as u64 {
+            return Err(HeaderError::InvalidBackupLba);
+        }
+        if self.part_start <= self.last_usable || self.part_start >= self.my_lba {
+            return Err(HeaderError::InvalidPartitionArrayLba);
+        }
+        if self.first_usable > self.last_usable || self.first_usable <= self.backup_lba + 1 {
+            return Err(HeaderError::InvalidUsableRange);
+        }
+        Ok(())
     }
-    Ok(())
 }
 
-impl Display for Header {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(
-            f,
-            "Header{{\n 
-            \trevision: {}.{},\n
-            \theader_size: {},\n
-            \tcrc32: {},\n
-            \tmy_lba: {},\n
-            \tbackup_lba: {},\n
-            \tfirst_usable: {},\n
-            \tlast_usable: {},\n
-            \tdisk_guid: {},\n
-            \tpart_start: {},\n
-            \tnum_parts: {},\n
-            \tpart_size: {},\n
-            \tcrc32_parts: {},\n}}",
-            self.revision.0,
-            self.revision.1,
-            self.header_size,
-            self.crc32,
-            self.my_lba,
-            self.backup_lba,
-            self.first_usable,
-            self.last_usable,
-            self.disk_guid,
-            self.part_start,
+/// Builds a fresh primary [`Header`], deriving the usable range from the partition array
+/// size and the position of the backup header.
+#[derive(Clone, Debug)]
+pub struct HeaderBuilder {
+    disk_guid: Uuid,
+    num_parts: u32,
+    part_size: u32,
+    block_size: u64,
+    backup_lba: Option<u64>,
+}
+
+impl Default for HeaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeaderBuilder {
+    /// A builder for the standard 128 entries of 128 bytes on 512 byte blocks.
+    pub fn new() -> Self {
+        Self {
+            disk_guid: Uuid::default(),
+            num_parts: MIN_PARTITION_NUM as u32,
+            part_size: PARTITION_LBA_SIZE as u32,
+            block_size: 512,
+            backup_lba: None,
+        }
+    }
+
+    pub fn disk_guid(mut self, disk_guid: Uuid) -> Self {
+        self.disk_guid = disk_guid;
+        self
+    }
+
+    pub fn num_parts(mut self, num_parts: u32) -> Self {
+        self.num_parts = num_parts;
+        self
+    }
+
+    pub fn part_size(mut self, part_size: u32) -> Self {
+        self.part_size = part_size;
+        self
+    }
+
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// LBA of the backup header, normally the last LBA of the disk.
+    pub fn backup_lba(mut self, backup_lba: u64) -> Self {
+        self.backup_lba = Some(backup_lba);
+        self
+    }
+
+    /// Pick `num_parts` from the disk capacity: 128 entries for normal disks, fewer on
+    /// small media so the arrays don't take more than 1/2048 of the disk, but always at
+    /// least one sector worth of entries. Uses the `part_size` set so far.
+    pub fn auto_num_parts(mut self, disk_sectors: u64, sector_size: u64) -> Self {
+        let part_size = (self.part_size as u64).max(1);
+        let per_sector = (sector_size / part_size).max(1);
+        let budget = disk_sectors.saturating_mul(sector_size) / 2048 / part_size;
+        let min = per_sector.min(MIN_PARTITION_NUM as u64);
+        let num_parts = (budget / per_sector * per_sector).clamp(min, MIN_PARTITION_NUM as u64);
+        self.num_parts = num_parts as u32;
+        self
+    }
+
+    /// Errors with [`HeaderError::NilDiskGuid`] unless a disk GUID was set and with
+    /// [`HeaderError::InvalidPartitionEntrySize`] unless `part_size` is a non zero multiple
+    /// of 128. A `backup_lba` of `u64::MAX` leaves no room for the disk and fails with
+    /// [`HeaderError::InvalidBackupLba`].
+    pub fn build(&self) -> Result<Header, HeaderError> {
+        if self.disk_guid.is_nil() {
+            return Err(HeaderError::NilDiskGuid);
+        }
+        let backup_lba = self.backup_lba.ok_or(HeaderError::MissingBackupLba)?;
+        let disk_lba_count = backup_lba
+            .checked_add(1)
+            .ok_or(HeaderError::InvalidBackupLba)?;
+        let mut header = Header::with_geometry(
+            disk_lba_count,
             self.num_parts,
             self.part_size,
-            self.crc32_parts
+            self.block_size,
         )
+        .map_err(|err| match err {
+            HeaderError::ToSmallForBackup => HeaderError::BackupLbaToEarly,
+            err => err,
+        })?;
+        header.disk_guid = self.disk_guid;
+        Ok(header)
+    }
+}
+
+/// Where the backup structures of a standard layout live.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BackupLayout {
+    /// The last LBA of the disk
+    pub backup_header_lba: u64,
+    /// First LBA of the backup partition array, which ends right before the header
+    pub backup_table_start_lba: u64,
+}
+
+/// The standard placement of the backup header and partition array on a disk of
+/// `disk_lba_count` LBAs with `num_parts` entries of `part_size` bytes.
+pub fn backup_layout(
+    disk_lba_count: u64,
+    num_parts: u32,
+    part_size: u32,
+    block_size: u64,
+) -> BackupLayout {
+    let backup_header_lba = disk_lba_count.saturating_sub(1);
+    let table = table_sectors(num_parts, part_size, block_size);
+    BackupLayout {
+        backup_header_lba,
+        backup_table_start_lba: backup_header_lba.saturating_sub(table),
+    }
+}
+
+/// Number of blocks of `block_size` bytes `num_parts` entries of `part_size` bytes take, a
+/// `block_size` of 0 counts as 1 instead of dividing by zero.
+fn table_sectors(num_parts: u32, part_size: u32, block_size: u64) -> u64 {
+    (num_parts as u64 * part_size as u64).div_ceil(block_size.max(1))
+}
+
+/// Entries must hold the 128 bytes of standard fields and stay 128 byte aligned.
+fn check_part_size(part_size: u32) -> Result<(), HeaderError> {
+    let part_size = part_size as usize;
+    if part_size < PARTITION_LBA_SIZE || !part_size.is_multiple_of(PARTITION_LBA_SIZE) {
+        return Err(HeaderError::InvalidPartitionEntrySize);
+    }
+    Ok(())
+}
+
+pub(crate) fn check_signature(sig: &[u8]) -> Result<(), HeaderError> {
+    if sig != GPT_SIGNATURE_BYTES {
+        return Err(HeaderError::InvalidGptSignature);
+    }
+    Ok(())
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Header {{")?;
+        writeln!(f, "    revision: {}", self.revision)?;
+        writeln!(f, "    header_size: {}", self.header_size)?;
+        writeln!(f, "    crc32: {:#010x}", self.crc32)?;
+        writeln!(f, "    my_lba: {}", self.my_lba)?;
+        writeln!(f, "    backup_lba: {}", self.backup_lba)?;
+        writeln!(f, "    first_usable: {}", self.first_usable)?;
+        writeln!(f, "    last_usable: {}", self.last_usable)?;
+        writeln!(f, "    disk_guid: {}", self.disk_guid)?;
+        writeln!(f, "    part_start: {}", self.part_start)?;
+        writeln!(f, "    num_parts: {}", self.num_parts)?;
+        writeln!(f, "    part_size: {}", self.part_size)?;
+        writeln!(f, "    crc32_parts: {:#010x}", self.crc32_parts)?;
+        write!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn crc32_covers_the_padded_header_size() {
+        let mut header = testing::layout().primary_header().clone();
+        header.header_size = 128;
+        let mut bytes = [0u8; 128];
+        bytes[..HEADER_SIZE].copy_from_slice(&header.serialize());
+        bytes[16..20].fill(0);
+        assert_eq!(header.calculate_crc32(), crc::crc32(&bytes));
+        assert_ne!(header.calculate_crc32(), crc::crc32(&bytes[..HEADER_SIZE]));
+    }
+
+    #[test]
+    fn deserialize_rejects_a_short_buffer() {
+        let bytes = testing::layout().primary_header().serialize();
+        assert!(matches!(
+            Header::deserialize(&bytes[..HEADER_SIZE - 1]),
+            Err(HeaderError::InvalidHeaderLength)
+        ));
+        assert!(matches!(
+            Header::deserialize(&bytes[..4]),
+            Err(HeaderError::InvalidHeaderLength)
+        ));
+    }
+
+    #[test]
+    fn validate_primary_rejects_last_usable_at_the_end_of_u64() {
+        let mut header = testing::layout().primary_header().clone();
+        header.backup_lba = u64::MAX - 1;
+        header.last_usable = u64::MAX;
+        assert!(matches!(
+            header.validate_primary(u64::MAX),
+            Err(HeaderError::InvalidUsableRange)
+        ));
+    }
+
+    #[test]
+    fn auto_num_parts_shrinks_the_table_on_tiny_disks() {
+        let guid = Uuid::from_bytes([0x12; 16]);
+        let tiny = HeaderBuilder::new()
+            .disk_guid(guid)
+            .auto_num_parts(32768, 512)
+            .backup_lba(32767)
+            .build()
+            .unwrap();
+        assert_eq!(tiny.num_parts, 64);
+        assert_eq!(tiny.first_usable, 2 + 16);
+        let normal = HeaderBuilder::new()
+            .disk_guid(guid)
+            .auto_num_parts(2_000_000, 512)
+            .backup_lba(1_999_999)
+            .build()
+            .unwrap();
+        assert_eq!(normal.num_parts, 128);
+        assert_eq!(
+            (normal.first_usable, normal.last_usable),
+            (34, 1_999_999 - 33)
+        );
+    }
+
+    #[test]
+    fn with_geometry_derives_the_standard_usable_range() {
+        // 100 MiB of 512 byte blocks
+        let header = Header::with_geometry(204800, 128, 128, 512).unwrap();
+        assert_eq!(
+            (
+                header.part_start,
+                header.first_usable,
+                header.last_usable,
+                header.backup_lba
+            ),
+            (2, 34, 204766, 204799)
+        );
+        // MBR, two headers and two 32 sector arrays leave nothing usable on 66 LBAs
+        assert!(matches!(
+            Header::with_geometry(66, 128, 128, 512),
+            Err(HeaderError::ToSmallForBackup)
+        ));
+        let smallest = Header::with_geometry(68, 128, 128, 512).unwrap();
+        assert_eq!(smallest.first_usable, smallest.last_usable);
+    }
+
+    #[test]
+    fn validate_header_size_keeps_the_header_in_its_sector() {
+        let mut header = testing::layout().primary_header().clone();
+        header.validate_header_size(512).unwrap();
+        for header_size in [5000, 513, 91, 0, u32::MAX] {
+            header.header_size = header_size;
+            assert!(matches!(
+                header.validate_header_size(512),
+                Err(HeaderError::InvalidHeaderSize)
+            ));
+        }
+        header.header_size = 512;
+        header.validate_header_size(512).unwrap();
+        // deserialize rejects the same header
+        let mut blk = [0u8; 512];
+        header.header_size = 5000;
+        blk[..HEADER_SIZE].copy_from_slice(&header.serialize());
+        assert!(matches!(
+            Header::deserialize(&blk),
+            Err(HeaderError::InvalidHeaderSize)
+        ));
+    }
+
+    #[test]
+    fn reserved_field_round_trips() {
+        let mut header = testing::layout().primary_header().clone();
+        header.reserved = 0xDEAD_BEEF;
+        let bytes = header.serialize();
+        assert_eq!(&bytes[20..24], &0xDEAD_BEEFu32.to_le_bytes());
+        assert_eq!(Header::deserialize(&bytes).unwrap().reserved, 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn display_prints_one_field_per_line() {
+        let header = Header::new_primary(Uuid::from_bytes([0x12; 16]), 8192, 512).unwrap();
+        let text = header.to_string();
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "Header {",
+                "    revision: 1.0",
+                "    header_size: 92",
+                "    crc32: 0x00000000",
+                "    my_lba: 1",
+                "    backup_lba: 8191",
+                "    first_usable: 34",
+                "    last_usable: 8158",
+                "    disk_guid: 12121212-1212-1212-1212-121212121212",
+                "    part_start: 2",
+                "    num_parts: 128",
+                "    part_size: 128",
+                "    crc32_parts: 0x00000000",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn backup_layout_of_a_one_gib_disk() {
+        let layout = backup_layout(2_097_152, 128, 128, 512);
+        assert_eq!(
+            layout,
+            BackupLayout {
+                backup_header_lba: 2_097_151,
+                backup_table_start_lba: 2_097_119,
+            }
+        );
+    }
+
+    #[test]
+    fn nil_disk_guid_is_rejected() {
+        let built = HeaderBuilder::new()
+            .backup_lba(testing::DISK_LBAS - 1)
+            .build();
+        assert!(matches!(built, Err(HeaderError::NilDiskGuid)));
+        let primary = Header::new_primary(Uuid::default(), testing::DISK_LBAS, 512);
+        assert!(matches!(primary, Err(HeaderError::NilDiskGuid)));
+        let guid = Uuid::from([0x12; 16]);
+        let header = HeaderBuilder::new()
+            .disk_guid(guid)
+            .backup_lba(testing::DISK_LBAS - 1)
+            .build()
+            .unwrap();
+        assert_eq!(header.disk_guid, guid);
+    }
+
+    #[test]
+    fn table_sectors_of_the_standard_array() {
+        let header = testing::layout().primary_header().clone();
+        assert_eq!(header.table_sectors(512), 32);
+        assert_eq!(header.table_sectors(4096), 4);
+        assert_eq!(table_sectors(129, 128, 512), 33);
+        assert_eq!(testing::layout().tables_fit(512), (true, true));
+    }
+
+    #[test]
+    fn entry_size_is_checked_when_building() {
+        for part_size in [0, 64, 200] {
+            assert!(matches!(
+                Header::with_geometry(testing::DISK_LBAS, 128, part_size, 512),
+                Err(HeaderError::InvalidPartitionEntrySize)
+            ));
+            let built = HeaderBuilder::new()
+                .disk_guid(Uuid::from([0x12; 16]))
+                .part_size(part_size)
+                .backup_lba(testing::DISK_LBAS - 1)
+                .build();
+            assert!(matches!(built, Err(HeaderError::InvalidPartitionEntrySize)));
+        }
+        assert!(Header::with_geometry(testing::DISK_LBAS, 128, 256, 512).is_ok());
+    }
+
+    #[test]
+    fn block_size_is_checked_when_building() {
+        for block_size in [0, 520] {
+            assert!(matches!(
+                Header::with_geometry(testing::DISK_LBAS, 128, 128, block_size),
+                Err(HeaderError::InvalidBlockSize)
+            ));
+            assert!(matches!(
+                Header::new_primary(Uuid::from([0x12; 16]), testing::DISK_LBAS, block_size),
+                Err(HeaderError::InvalidBlockSize)
+            ));
+        }
+        assert!(Header::with_geometry(testing::DISK_LBAS, 128, 128, 4096).is_ok());
+        assert_eq!(
+            backup_layout(testing::DISK_LBAS, 128, 128, 0).backup_header_lba,
+            8191
+        );
+    }
+
+    #[test]
+    fn huge_inputs_do_not_overflow_the_builder() {
+        let builder = HeaderBuilder::new().auto_num_parts(u64::MAX, 4096);
+        assert_eq!(builder.num_parts, MIN_PARTITION_NUM as u32);
+        let built = HeaderBuilder::new()
+            .disk_guid(Uuid::from([0x12; 16]))
+            .backup_lba(u64::MAX)
+            .build();
+        assert!(matches!(built, Err(HeaderError::InvalidBackupLba)));
+    }
+
+    /// The error `check` gives for `header` after `edit`.
+    fn placement_error(
+        header: &Header,
+        edit: fn(&mut Header),
+        check: fn(&Header, u64) -> Result<(), HeaderError>,
+    ) -> HeaderError {
+        let mut header = header.clone();
+        edit(&mut header);
+        check(&header, testing::DISK_LBAS).unwrap_err()
+    }
+
+    #[test]
+    fn validate_primary_checks_the_placement() {
+        let layout = testing::layout();
+        let primary = layout.primary_header();
+        assert!(primary.validate_primary(testing::DISK_LBAS).is_ok());
+        assert!(primary.validate_backup(testing::DISK_LBAS).is_err());
+        let error = |edit| placement_error(primary, edit, Header::validate_primary);
+        assert!(matches!(error(|h| h.my_lba = 0), HeaderError::InvalidMyLba));
+        assert!(matches!(
+            error(|h| h.backup_lba -= 1),
+            HeaderError::InvalidBackupLba
+        ));
+        assert!(matches!(
+            error(|h| h.part_start = 1),
+            HeaderError::InvalidPartitionArrayLba
+        ));
+        assert!(matches!(
+            error(|h| h.part_start = h.first_usable),
+            HeaderError::InvalidPartitionArrayLba
+        ));
+        assert!(matches!(
+            error(|h| h.last_usable = h.first_usable - 1),
+            HeaderError::InvalidUsableRange
+        ));
+        assert!(matches!(
+            error(|h| h.last_usable = h.backup_lba - 1),
+            HeaderError::InvalidUsableRange
+        ));
+    }
+
+    #[test]
+    fn validate_backup_checks_the_placement() {
+        let layout = testing::layout();
+        let backup = layout.backup_header();
+        assert!(backup.validate_backup(testing::DISK_LBAS).is_ok());
+        assert!(backup.validate_primary(testing::DISK_LBAS).is_err());
+        let error = |edit| placement_error(backup, edit, Header::validate_backup);
+        assert!(matches!(error(|h| h.my_lba = 1), HeaderError::InvalidMyLba));
+        assert!(matches!(
+            error(|h| h.backup_lba = 2),
+            HeaderError::InvalidBackupLba
+        ));
+        assert!(matches!(
+            error(|h| h.part_start = h.last_usable),
+            HeaderError::InvalidPartitionArrayLba
+        ));
+        assert!(matches!(
+            error(|h| h.part_start = h.my_lba),
+            HeaderError::InvalidPartitionArrayLba
+        ));
+        assert!(matches!(
+            error(|h| h.first_usable = 2),
+            HeaderError::InvalidUsableRange
+        ));
+        assert!(matches!(
+            error(|h| h.first_usable = h.last_usable + 1),
+            HeaderError::InvalidUsableRange
+        ));
     }
 }
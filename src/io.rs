@@ -0,0 +1,190 @@
+//! Minimal I/O traits so the crate can pull a disk image from any byte source in `no_std`.
+
+use alloc::{vec, vec::Vec};
+
+use super::BlockError;
+
+/// A source of bytes that can only be read front to back, like a pipe.
+pub trait Read {
+    /// Read up to `buf.len()` bytes into `buf`, returning how many were read, 0 means the
+    /// end of the stream was reached.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BlockError>;
+
+    /// Fill the whole `buf`, failing with [`BlockError::UnexpectedEof`] if the stream ends
+    /// first.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<(), BlockError> {
+        while !buf.is_empty() {
+            let read = self.read(buf)?;
+            if read == 0 {
+                return Err(BlockError::UnexpectedEof);
+            }
+            buf = &mut buf[read..];
+        }
+        Ok(())
+    }
+}
+
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BlockError> {
+        let len = buf.len().min(self.len());
+        let (head, tail) = self.split_at(len);
+        buf[..len].copy_from_slice(head);
+        *self = tail;
+        Ok(len)
+    }
+}
+
+/// A disk addressed by LBA, the way to plug a block device driver into the parser.
+pub trait BlockRead {
+    /// Size of a block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Number of blocks on the disk.
+    fn block_count(&self) -> u64;
+
+    /// Read `buf.len() / block_size` consecutive blocks starting at `lba` into `buf`.
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+}
+
+/// A disk that can also be written, what [`GptLayout::store`](crate::GptLayout::store)
+/// needs to put a layout back.
+pub trait BlockIo: BlockRead {
+    /// Write `buf.len() / block_size` consecutive blocks starting at `lba` from `buf`.
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+}
+
+/// Fail with [`BlockError::InvalidBlockSize`] for a block size of zero, which every LBA
+/// computation would divide by.
+pub(crate) fn check_block_size(block_size: usize) -> Result<(), BlockError> {
+    if block_size == 0 {
+        return Err(BlockError::InvalidBlockSize);
+    }
+    Ok(())
+}
+
+/// A whole disk image held in memory.
+pub(crate) struct SliceDisk<'a> {
+    disk: &'a [u8],
+    block_size: usize,
+}
+
+impl<'a> SliceDisk<'a> {
+    pub(crate) fn new(disk: &'a [u8], block_size: usize) -> Result<Self, BlockError> {
+        check_block_size(block_size)?;
+        Ok(Self { disk, block_size })
+    }
+}
+
+impl BlockRead for SliceDisk<'_> {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.disk.len() / self.block_size) as u64
+    }
+
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let start = lba as usize * self.block_size;
+        let blocks = self
+            .disk
+            .get(start..start + buf.len())
+            .ok_or(BlockError::OutOfRange)?;
+        buf.copy_from_slice(blocks);
+        Ok(())
+    }
+}
+
+/// A disk image owned in memory, a mock device for tests and tooling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemDisk {
+    disk: Vec<u8>,
+    block_size: usize,
+}
+
+impl MemDisk {
+    /// A zeroed disk of `block_count` blocks of `block_size` bytes. Fails with
+    /// [`BlockError::InvalidBlockSize`] if `block_size` is zero.
+    pub fn new(block_count: u64, block_size: usize) -> Result<Self, BlockError> {
+        check_block_size(block_size)?;
+        Ok(Self {
+            disk: vec![0u8; block_count as usize * block_size],
+            block_size,
+        })
+    }
+
+    /// A disk holding the image `disk`, a trailing partial block is not addressable. Fails
+    /// with [`BlockError::InvalidBlockSize`] if `block_size` is zero.
+    pub fn from_vec(disk: Vec<u8>, block_size: usize) -> Result<Self, BlockError> {
+        check_block_size(block_size)?;
+        Ok(Self { disk, block_size })
+    }
+
+    /// The whole image.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.disk
+    }
+
+    /// The byte range of `len` bytes from `lba`, if it lies on the disk.
+    fn range(&self, lba: u64, len: usize) -> Result<core::ops::Range<usize>, BlockError> {
+        let start = usize::try_from(lba)
+            .ok()
+            .and_then(|lba| lba.checked_mul(self.block_size))
+            .ok_or(BlockError::OutOfRange)?;
+        let end = start.checked_add(len).ok_or(BlockError::OutOfRange)?;
+        if end > self.block_count() as usize * self.block_size {
+            return Err(BlockError::OutOfRange);
+        }
+        Ok(start..end)
+    }
+}
+
+impl BlockRead for MemDisk {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.disk.len() / self.block_size) as u64
+    }
+
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let range = self.range(lba, buf.len())?;
+        buf.copy_from_slice(&self.disk[range]);
+        Ok(())
+    }
+}
+
+impl BlockIo for MemDisk {
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let range = self.range(lba, buf.len())?;
+        self.disk[range].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, GptLayout};
+
+    #[test]
+    fn mem_disk_round_trips_a_layout() {
+        let mut disk = MemDisk::new(testing::DISK_LBAS, 512).unwrap();
+        assert_eq!(disk.as_slice().len(), testing::DISK_LBAS as usize * 512);
+        testing::layout().store(&mut disk).unwrap();
+        assert_eq!(GptLayout::load(&mut disk).unwrap(), testing::layout());
+        assert!(matches!(
+            disk.write_block(testing::DISK_LBAS, &[0u8; 512]),
+            Err(BlockError::OutOfRange)
+        ));
+        assert!(matches!(
+            disk.read_block(testing::DISK_LBAS - 1, &mut [0u8; 1024]),
+            Err(BlockError::OutOfRange)
+        ));
+        assert!(matches!(
+            disk.read_block(u64::MAX, &mut [0u8; 512]),
+            Err(BlockError::OutOfRange)
+        ));
+    }
+}
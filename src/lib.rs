@@ -1,172 +1,986 @@
 #![no_std]
+#[cfg(feature = "std")]
+extern crate std;
+
+mod crc;
+mod diff;
 mod err;
 mod hdr;
+pub mod io;
 mod mbr;
+mod parse;
+pub mod part_types;
 mod partition;
+#[cfg(feature = "std")]
+mod stdio;
 mod uuid;
 
-use core::mem::MaybeUninit;
-
 extern crate alloc;
 
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, vec, vec::Vec};
 use byteorder::{ByteOrder, LittleEndian};
+pub use diff::{LayoutDiff, PartitionChange};
 pub use err::*;
 pub use hdr::*;
 
 pub use mbr::*;
+pub use parse::{ParseOptions, MAX_STREAM_IMAGE_SIZE};
 pub use partition::*;
 pub use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GptLayout {
-    protective_mbr: Box<MaybeUninit<ProtectiveMBR>>,
-    primary_header: Box<MaybeUninit<Header>>,
+    protective_mbr: Option<Box<ProtectiveMBR>>,
+    primary_header: Option<Box<Header>>,
     partitions: Vec<(Partition, usize)>,
     backup_partitions: Vec<(Partition, usize)>,
-    backup_header: Box<MaybeUninit<Header>>,
+    /// Position in `partitions` of the entry in each one-based slot, see [`index_slots`]
+    slots: Vec<Option<usize>>,
+    backup_slots: Vec<Option<usize>>,
+    /// Entries with a nil type GUID but leftover data, see [`GptLayout::ghost_entries`]
+    ghosts: Vec<(Partition, usize)>,
+    backup_ghosts: Vec<(Partition, usize)>,
+    backup_header: Option<Box<Header>>,
+    /// What [`GptLayout::load_with_options`] let through, see [`GptLayout::warnings`]
+    warnings: Vec<GptWarning>,
+}
+
+/// Two layouts are equal when the same structures are initialized with the same content
+/// and every slot holds the same entry, regardless of the order of the partition vecs.
+impl PartialEq for GptLayout {
+    fn eq(&self, other: &Self) -> bool {
+        self.protective_mbr == other.protective_mbr
+            && self.primary_header == other.primary_header
+            && self.backup_header == other.backup_header
+            && same_slots(&self.partitions, &other.partitions)
+            && same_slots(&self.backup_partitions, &other.backup_partitions)
+            && same_slots(&self.ghosts, &other.ghosts)
+            && same_slots(&self.backup_ghosts, &other.backup_ghosts)
+    }
+}
+
+impl Eq for GptLayout {}
+
+impl Default for GptLayout {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GptLayout {
     pub fn new() -> Self {
+        Self::with_capacity(MIN_PARTITION_NUM)
+    }
+
+    /// An empty layout with room for `num_parts` partitions in each array, for headers
+    /// declaring more than the usual 128 entries.
+    pub fn with_capacity(num_parts: usize) -> Self {
         Self {
-            protective_mbr: Box::new_uninit(),
-            primary_header: Box::new_uninit(),
-            partitions: Vec::with_capacity(PARTITION_LBA_SIZE),
-            backup_partitions: Vec::with_capacity(PARTITION_LBA_SIZE),
-            backup_header: Box::new_uninit(),
+            protective_mbr: None,
+            primary_header: None,
+            partitions: Vec::with_capacity(num_parts),
+            backup_partitions: Vec::with_capacity(num_parts),
+            slots: Vec::new(),
+            backup_slots: Vec::new(),
+            ghosts: Vec::new(),
+            backup_ghosts: Vec::new(),
+            backup_header: None,
+            warnings: Vec::new(),
         }
     }
 
     pub fn init_primary_header(&mut self, blk: &[u8]) -> Result<(), HeaderError> {
-        let header = Header::deserialize(&blk)?;
-        let init = MaybeUninit::new(header);
-        self.primary_header = Box::new(init);
+        let header = Header::deserialize(blk)?;
+        self.set_primary_header(header);
         Ok(())
     }
 
     pub fn init_backup_header(&mut self, blk: &[u8]) -> Result<(), HeaderError> {
-        let header = Header::deserialize(&blk)?;
-        let init = MaybeUninit::new(header);
-        self.backup_header = Box::new(init);
+        let header = Header::deserialize(blk)?;
+        self.set_backup_header(header);
         Ok(())
     }
 
     pub fn init_protective_mbr(&mut self, blk: &[u8]) -> Result<(), MBRError> {
-        let mbr = ProtectiveMBR::deserialize(&blk)?;
-        let init = MaybeUninit::new(mbr);
-        self.protective_mbr = Box::new(init);
+        let mbr = ProtectiveMBR::deserialize(blk)?;
+        self.set_protective_mbr(mbr);
         Ok(())
     }
 
-    pub fn init_partitions(&mut self, blk: &[u8], entry_index: usize) {
-        let part_num = blk.len() / PARTITION_LBA_SIZE;
+    fn set_protective_mbr(&mut self, mbr: ProtectiveMBR) {
+        self.protective_mbr = Some(Box::new(mbr));
+    }
+
+    fn set_primary_header(&mut self, header: Header) {
+        self.primary_header = Some(Box::new(header));
+    }
+
+    fn set_backup_header(&mut self, header: Header) {
+        self.backup_header = Some(Box::new(header));
+    }
+
+    /// Decode the entries in `blk`, the `entry_index`-th (one-based) block of the primary
+    /// partition array. Entries are `part_size` bytes apart as the primary header says, so
+    /// it has to be initialized first or this fails with [`PartitionError::MissingHeader`],
+    /// and entries must not straddle blocks, use [`GptLayout::init_all_partitions`] when
+    /// they do.
+    pub fn init_partitions(
+        &mut self,
+        blk: &[u8],
+        entry_index: usize,
+    ) -> Result<(), PartitionError> {
+        let part_size = self
+            .primary_header
+            .as_deref()
+            .ok_or(PartitionError::MissingHeader)?
+            .part_size as usize;
+        if part_size < PARTITION_LBA_SIZE {
+            return Err(PartitionError::InvalidEntryLength);
+        }
+        let part_num = blk.len() / part_size;
         let part_index = (entry_index - 1) * part_num;
-        (0..part_num).for_each(|index| {
-            let start = index * PARTITION_LBA_SIZE;
-            let end = start + PARTITION_LBA_SIZE;
-            if let Some(part) = Partition::deserialize(&blk[start..end]) {
-                self.partitions.push((part, part_index + index + 1));
-            }
-        });
+        (0..part_num).try_for_each(|index| {
+            let start = index * part_size;
+            let end = start + part_size;
+            let entry = &blk[start..end];
+            decode_entry(
+                entry,
+                part_index + index + 1,
+                &mut self.partitions,
+                &mut self.ghosts,
+            )
+        })?;
+        self.slots = index_slots(&self.partitions);
+        Ok(())
     }
 
-    pub fn init_backup_partitions(&mut self, blk: &[u8], entry_index: usize) {
-        let part_num = blk.len() / PARTITION_LBA_SIZE;
+    /// Decode the entries of the `entry_index`-th (one-based) block of the backup partition
+    /// array, see [`GptLayout::init_partitions`].
+    pub fn init_backup_partitions(
+        &mut self,
+        blk: &[u8],
+        entry_index: usize,
+    ) -> Result<(), PartitionError> {
+        let part_size = self
+            .backup_header
+            .as_deref()
+            .ok_or(PartitionError::MissingHeader)?
+            .part_size as usize;
+        if part_size < PARTITION_LBA_SIZE {
+            return Err(PartitionError::InvalidEntryLength);
+        }
+        let part_num = blk.len() / part_size;
         let part_index = (entry_index - 1) * part_num;
-        (0..part_num).for_each(|index| {
-            let start = index * PARTITION_LBA_SIZE;
-            let end = start + PARTITION_LBA_SIZE;
-            if let Some(part) = Partition::deserialize(&blk[start..end]) {
-                self.backup_partitions.push((part, part_index + index + 1));
-            }
-        });
+        (0..part_num).try_for_each(|index| {
+            let start = index * part_size;
+            let end = start + part_size;
+            let entry = &blk[start..end];
+            decode_entry(
+                entry,
+                part_index + index + 1,
+                &mut self.backup_partitions,
+                &mut self.backup_ghosts,
+            )
+        })?;
+        self.backup_slots = index_slots(&self.backup_partitions);
+        Ok(())
+    }
+
+    /// Decode a whole partition array read in one go, `num_parts` entries of `part_size`
+    /// bytes, each recorded with its one-based index.
+    pub fn init_all_partitions(
+        &mut self,
+        array_bytes: &[u8],
+        num_parts: u32,
+        part_size: u32,
+    ) -> Result<(), PartitionError> {
+        decode_table(
+            array_bytes,
+            num_parts,
+            part_size,
+            &mut self.partitions,
+            &mut self.ghosts,
+        )?;
+        self.slots = index_slots(&self.partitions);
+        Ok(())
+    }
+
+    /// Decode a whole backup partition array read in one go, see
+    /// [`GptLayout::init_all_partitions`].
+    pub fn init_all_backup_partitions(
+        &mut self,
+        array_bytes: &[u8],
+        num_parts: u32,
+        part_size: u32,
+    ) -> Result<(), PartitionError> {
+        decode_table(
+            array_bytes,
+            num_parts,
+            part_size,
+            &mut self.backup_partitions,
+            &mut self.backup_ghosts,
+        )?;
+        self.backup_slots = index_slots(&self.backup_partitions);
+        Ok(())
     }
 }
 
 impl GptLayout {
+    /// Panics if the protective MBR hasn't been initialized yet.
     pub fn protective_mbr(&self) -> &ProtectiveMBR {
-        unsafe { self.protective_mbr.as_ref().assume_init_ref() }
+        self.protective_mbr
+            .as_deref()
+            .expect("the protective MBR is not initialized, call init_protective_mbr first")
     }
 
+    /// Panics if the primary header hasn't been initialized yet.
     pub fn primary_header(&self) -> &Header {
-        unsafe { self.primary_header.as_ref().assume_init_ref() }
+        self.primary_header
+            .as_deref()
+            .expect("the primary header is not initialized, call init_primary_header first")
     }
+    /// O(1), the position of the slot is looked up in an index kept up to date with the
+    /// partition vec. `None` for an empty slot or one past the table's `num_parts`.
     pub fn partition(&self, part_index: usize) -> Option<&Partition> {
-        assert!(part_index < MIN_PARTITION_NUM);
-        let mut find_index = PARTITION_LBA_SIZE;
-        for (i, (_, part_i)) in self.partitions.iter().enumerate() {
-            if *part_i == part_index {
-                find_index = i;
-                break;
+        let position = self.slots.get(part_index).copied().flatten()?;
+        Some(&self.partitions[position].0)
+    }
+    /// O(1), the position of the slot is looked up in an index kept up to date with the
+    /// partition vec. `None` for an empty slot or one past the table's `num_parts`.
+    pub fn backup_partition(&self, part_index: usize) -> Option<&Partition> {
+        let position = self.backup_slots.get(part_index).copied().flatten()?;
+        Some(&self.backup_partitions[position].0)
+    }
+    /// Panics if the backup header hasn't been initialized yet.
+    pub fn backup_header(&self) -> &Header {
+        self.backup_header
+            .as_deref()
+            .expect("the backup header is not initialized, call init_backup_header first")
+    }
+
+    /// The populated primary partitions with their one-based indices.
+    pub fn partitions(&self) -> &[(Partition, usize)] {
+        &self.partitions
+    }
+
+    /// The populated backup partitions with their one-based indices.
+    pub fn backup_partitions(&self) -> &[(Partition, usize)] {
+        &self.backup_partitions
+    }
+
+    /// Number of populated partitions in the primary array, 0 for a header with
+    /// `num_parts = 0`.
+    pub fn partition_count(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// Sectors taken by the primary partitions, the sum of their sizes. Overlapping
+    /// partitions, which are invalid, get counted twice.
+    pub fn used_sectors(&self) -> u64 {
+        self.partitions
+            .iter()
+            .map(|(part, _)| part.size_lba())
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Sectors of the primary header's usable range, cut at the end of a disk of
+    /// `disk_lba_count` LBAs, that no partition uses. See [`GptLayout::used_sectors`].
+    pub fn free_sectors(&self, disk_lba_count: u64) -> u64 {
+        let header = self.primary_header();
+        let last_usable = header.last_usable.min(disk_lba_count.saturating_sub(1));
+        let usable = (last_usable + 1).saturating_sub(header.first_usable);
+        usable.saturating_sub(self.used_sectors())
+    }
+
+    /// The primary partition whose unique `part_guid` is `guid`, with its one-based index.
+    pub fn partition_by_guid(&self, guid: &Uuid) -> Option<(usize, &Partition)> {
+        self.partitions
+            .iter()
+            .find(|(part, _)| part.part_guid == *guid)
+            .map(|(part, index)| (*index, part))
+    }
+
+    /// All primary partitions of type `type_guid` with their one-based indices, e.g. every
+    /// EFI system partition.
+    pub fn partition_by_type<'a>(
+        &'a self,
+        type_guid: &'a Uuid,
+    ) -> impl Iterator<Item = (usize, &'a Partition)> + 'a {
+        self.partitions
+            .iter()
+            .filter(move |(part, _)| part.part_type_guid == *type_guid)
+            .map(|(part, index)| (*index, part))
+    }
+
+    /// The distinct type GUIDs of the primary partitions in ascending order, empty slots
+    /// and their nil type are never included.
+    pub fn distinct_type_guids(&self) -> Vec<Uuid> {
+        let mut types: Vec<Uuid> = self
+            .partitions
+            .iter()
+            .map(|(part, _)| part.part_type_guid)
+            .filter(|guid| !guid.is_nil())
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+        types
+    }
+
+    /// All primary partitions whose `attrs & mask == value` with their one-based indices,
+    /// e.g. `(1 << 2, 1 << 2)` for the legacy BIOS bootable ones.
+    pub fn partitions_with_attr(
+        &self,
+        mask: u64,
+        value: u64,
+    ) -> impl Iterator<Item = (usize, &Partition)> {
+        self.partitions
+            .iter()
+            .filter(move |(part, _)| part.attrs & mask == value)
+            .map(|(part, index)| (*index, part))
+    }
+
+    /// The first primary partition labelled `name` with its one-based index. Names aren't
+    /// guaranteed to be unique, later partitions with the same name are not reported.
+    pub fn partition_by_name(&self, name: &str) -> Option<(usize, &Partition)> {
+        self.partitions
+            .iter()
+            .find(|(part, _)| part.name.to_string_lossy() == name)
+            .map(|(part, index)| (*index, part))
+    }
+
+    /// Whether the partition in the one-based `slot` ends last on the disk, i.e. no other
+    /// populated partition has a higher `end_lba`. `false` for an empty slot.
+    pub fn is_last_partition(&self, slot: usize) -> bool {
+        let Some(part) = self.partition(slot) else {
+            return false;
+        };
+        self.partitions
+            .iter()
+            .all(|(other, _)| other.end_lba <= part.end_lba)
+    }
+
+    /// Every slot of the primary array in order, `1..=num_parts`, with `None` for the empty
+    /// ones, unlike [`GptLayout::partitions`] which only holds the populated ones.
+    pub fn all_slots(&self) -> impl Iterator<Item = (usize, Option<&Partition>)> {
+        (1..=self.primary_header().num_parts as usize).map(|index| (index, self.partition(index)))
+    }
+
+    /// The entry in the one-based slot `index` of the primary array, unlike
+    /// [`GptLayout::partition`] an empty slot yields an unused partition (see
+    /// [`Partition::is_used`]), `None` means `index` is outside the array.
+    pub fn entry(&self, index: usize) -> Option<&Partition> {
+        if index == 0 || index > self.primary_header().num_parts as usize {
+            return None;
+        }
+        Some(self.partition(index).unwrap_or(&EMPTY_PARTITION))
+    }
+
+    /// Whether the (primary, backup) partition arrays fit where their headers put them for
+    /// blocks of `block_size` bytes: the primary one between LBA 1 and `first_usable`, the
+    /// backup one between `last_usable` and the backup header. An array whose end overflows
+    /// a `u64` doesn't fit.
+    pub fn tables_fit(&self, block_size: u64) -> (bool, bool) {
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        let primary_end = primary
+            .part_start
+            .checked_add(primary.table_sectors(block_size));
+        let backup_end = backup
+            .part_start
+            .checked_add(backup.table_sectors(block_size));
+        (
+            primary.part_start > PRIMARY_HEADER_LBA as u64
+                && primary_end.is_some_and(|end| end <= primary.first_usable),
+            backup.part_start > backup.last_usable
+                && backup_end.is_some_and(|end| end <= backup.my_lba),
+        )
+    }
+
+    /// Whether the (primary, backup) headers pass their CRC32 checks, both the header's own
+    /// `crc32` and the `crc32_parts` of the partition array it describes.
+    pub fn valid_headers(&self) -> (bool, bool) {
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        (
+            primary.crc32_valid()
+                && primary.crc32_parts == crc::crc32(&self.serialize_partitions()),
+            backup.crc32_valid()
+                && backup.crc32_parts == crc::crc32(&self.serialize_backup_partitions()),
+        )
+    }
+
+    /// Whether both headers describe the same disk, see [`Header::content_eq`], and their
+    /// LBAs point at each other.
+    pub fn primary_matches_backup(&self) -> bool {
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        primary.content_eq(backup)
+            && primary.my_lba == backup.backup_lba
+            && primary.backup_lba == backup.my_lba
+    }
+
+    /// Pairs of one-based indices of primary partitions sharing a `part_guid`, which the
+    /// spec forbids. Partitions with a nil GUID are skipped.
+    pub fn find_duplicate_guids(&self) -> Vec<(usize, usize)> {
+        let mut duplicates = Vec::new();
+        for (i, (part, index)) in self.partitions.iter().enumerate() {
+            if !part.part_guid.validate() {
+                continue;
+            }
+            for (other, other_index) in self.partitions[i + 1..].iter() {
+                if other.part_guid == part.part_guid {
+                    duplicates.push((*index, *other_index));
+                }
             }
         }
-        if find_index != PARTITION_LBA_SIZE {
-            Some(&self.partitions.get(find_index).unwrap().0)
-        } else {
-            None
+        duplicates
+    }
+
+    /// The first multiple of `alignment_lba` past `after_lba`, where a partition following
+    /// one that ends at `after_lba` should start.
+    pub fn next_aligned_start(after_lba: u64, alignment_lba: u64) -> u64 {
+        let alignment = alignment_lba.max(1);
+        (after_lba / alignment + 1) * alignment
+    }
+
+    /// Pairs of one-based indices of primary partitions whose LBA ranges intersect.
+    pub fn find_overlapping_partitions(&self) -> Vec<(usize, usize)> {
+        let mut overlaps = Vec::new();
+        for (i, (part, index)) in self.partitions.iter().enumerate() {
+            for (other, other_index) in self.partitions[i + 1..].iter() {
+                if part.start_lba <= other.end_lba && other.start_lba <= part.end_lba {
+                    overlaps.push((*index, *other_index));
+                }
+            }
         }
+        overlaps
     }
-    pub fn backup_partition(&self, part_index: usize) -> Option<&Partition> {
-        assert!(part_index < MIN_PARTITION_NUM);
-        let mut find_index = PARTITION_LBA_SIZE;
-        for (i, (_, part_i)) in self.backup_partitions.iter().enumerate() {
-            if *part_i == part_index {
-                find_index = i;
-                break;
+
+    /// Run every check on the layout for a disk of `disk_lba_count` LBAs and report all
+    /// the problems found: protective MBR, placement and CRCs of both headers, whether the
+    /// headers agree, and overlapping, duplicate or out of bounds partitions.
+    pub fn validate(&self, disk_lba_count: u64) -> Result<(), Vec<LayoutError>> {
+        let mut errors = Vec::new();
+        if let Err(err) = self.protective_mbr().validate(disk_lba_count) {
+            errors.push(LayoutError::InvalidProtectiveMbr(err));
+        }
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        if let Err(error) = primary.validate_primary(disk_lba_count) {
+            errors.push(LayoutError::InvalidHeader {
+                header: HeaderKind::Primary,
+                error,
+            });
+        }
+        if let Err(error) = backup.validate_backup(disk_lba_count) {
+            errors.push(LayoutError::InvalidHeader {
+                header: HeaderKind::Backup,
+                error,
+            });
+        }
+        for (kind, header, array) in [
+            (HeaderKind::Primary, primary, self.serialize_partitions()),
+            (
+                HeaderKind::Backup,
+                backup,
+                self.serialize_backup_partitions(),
+            ),
+        ] {
+            if !header.crc32_valid() {
+                errors.push(LayoutError::HeaderCrcMismatch(kind));
+            }
+            if header.crc32_parts != crc::crc32(&array) {
+                errors.push(LayoutError::PartitionTableCrcMismatch(kind));
             }
         }
-        if find_index != PARTITION_LBA_SIZE {
-            Some(&self.backup_partitions.get(find_index).unwrap().0)
+        if !self.primary_matches_backup() {
+            errors.push(LayoutError::HeadersMismatch);
+        }
+        for (first, second) in self.find_overlapping_partitions() {
+            errors.push(LayoutError::PartitionsOverlap(first, second));
+        }
+        for (first, second) in self.find_duplicate_guids() {
+            errors.push(LayoutError::DuplicatePartitionGuid(first, second));
+        }
+        for index in self.find_metadata_overlaps() {
+            errors.push(LayoutError::OverlapsMetadata(index));
+        }
+        for index in self.validate_partition_bounds() {
+            let part = self.partition(index).unwrap_or(&EMPTY_PARTITION);
+            errors.push(LayoutError::PartitionOutOfBounds {
+                index,
+                start: part.start_lba,
+                end: part.end_lba,
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
         } else {
-            None
+            Err(errors)
         }
     }
-    pub fn backup_header(&self) -> &Header {
-        unsafe { self.backup_header.as_ref().assume_init_ref() }
+
+    /// One-based indices of the primary partitions overlapping GPT metadata: the MBR and
+    /// primary header at LBA 0 and 1, the primary array from `part_start` to
+    /// `first_usable`, or the backup array and header from the backup's `part_start` to
+    /// `backup_lba`. Stricter than [`GptLayout::validate_partition_bounds`], it doesn't
+    /// trust the usable range.
+    pub fn find_metadata_overlaps(&self) -> Vec<usize> {
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        let regions = [
+            (0, PRIMARY_HEADER_LBA as u64),
+            (primary.part_start, primary.first_usable.saturating_sub(1)),
+            (backup.part_start, primary.backup_lba),
+        ];
+        self.partitions
+            .iter()
+            .filter(|(part, _)| {
+                regions
+                    .iter()
+                    .any(|(start, end)| part.start_lba <= *end && *start <= part.end_lba)
+            })
+            .map(|(_, index)| *index)
+            .collect()
+    }
+
+    /// One-based indices of the primary partitions reaching outside the primary header's
+    /// `first_usable..=last_usable` range, they would overwrite GPT metadata.
+    pub fn validate_partition_bounds(&self) -> Vec<usize> {
+        let header = self.primary_header();
+        self.partitions
+            .iter()
+            .filter(|(part, _)| {
+                part.start_lba < header.first_usable || part.end_lba > header.last_usable
+            })
+            .map(|(_, index)| *index)
+            .collect()
+    }
+
+    /// The problems parsing let through. Besides the failed checks lenient [`ParseOptions`]
+    /// tolerate, strict parsing also reports the findings that never stop it: mismatched
+    /// headers, non standard entry sizes, ghost entries and misaligned or out of bounds
+    /// partitions.
+    pub fn warnings(&self) -> &[GptWarning] {
+        &self.warnings
+    }
+
+    /// One-based indices of the primary entries that have a nil type GUID but still
+    /// carry data, i.e. partitions that were deleted without being zeroed.
+    pub fn ghost_entries(&self) -> Vec<usize> {
+        self.ghosts.iter().map(|(_, index)| *index).collect()
     }
 }
 
 impl GptLayout {
+    /// Panics if the protective MBR hasn't been initialized yet.
     pub fn protective_mbr_mut(&mut self) -> &mut ProtectiveMBR {
-        unsafe { self.protective_mbr.as_mut().assume_init_mut() }
+        self.protective_mbr
+            .as_deref_mut()
+            .expect("the protective MBR is not initialized, call init_protective_mbr first")
     }
+    /// Panics if the primary header hasn't been initialized yet.
     pub fn primary_header_mut(&mut self) -> &mut Header {
-        unsafe { self.primary_header.as_mut().assume_init_mut() }
+        self.primary_header
+            .as_deref_mut()
+            .expect("the primary header is not initialized, call init_primary_header first")
     }
+    /// O(1), the position of the slot is looked up in an index kept up to date with the
+    /// partition vec. `None` for an empty slot or one past the table's `num_parts`.
     pub fn partition_mut(&mut self, part_index: usize) -> Option<&mut Partition> {
-        assert!(part_index < MIN_PARTITION_NUM);
-        let mut find_index = PARTITION_LBA_SIZE;
-        for (i, (_, part_i)) in self.partitions.iter().enumerate() {
-            if *part_i == part_index {
-                find_index = i;
-                break;
+        let position = self.slots.get(part_index).copied().flatten()?;
+        Some(&mut self.partitions[position].0)
+    }
+    /// O(1), the position of the slot is looked up in an index kept up to date with the
+    /// partition vec. `None` for an empty slot or one past the table's `num_parts`.
+    pub fn backup_partition_mut(&mut self, part_index: usize) -> Option<&mut Partition> {
+        let position = self.backup_slots.get(part_index).copied().flatten()?;
+        Some(&mut self.backup_partitions[position].0)
+    }
+    /// Panics if the backup header hasn't been initialized yet.
+    pub fn backup_header_mut(&mut self) -> &mut Header {
+        self.backup_header
+            .as_deref_mut()
+            .expect("the backup header is not initialized, call init_backup_header first")
+    }
+}
+
+impl GptLayout {
+    /// Serialize the primary partition array, `num_parts` entries of `part_size` bytes as
+    /// described by the primary header.
+    pub fn serialize_partitions(&self) -> Vec<u8> {
+        let header = self.primary_header();
+        serialize_table(
+            &self.partitions,
+            &self.ghosts,
+            header.num_parts as usize,
+            header.part_size as usize,
+        )
+    }
+
+    /// Serialize the backup partition array as described by the backup header.
+    pub fn serialize_backup_partitions(&self) -> Vec<u8> {
+        let header = self.backup_header();
+        serialize_table(
+            &self.backup_partitions,
+            &self.backup_ghosts,
+            header.num_parts as usize,
+            header.part_size as usize,
+        )
+    }
+
+    /// All the GPT metadata as laid out on disk, each structure padded to `sector_size`:
+    /// protective MBR, primary header, primary array, backup array and backup header.
+    /// Meant to be fed to a hash for change detection or signing.
+    pub fn metadata_digest_input(&self, sector_size: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        append_padded(&mut bytes, &self.protective_mbr().serialize(), sector_size);
+        append_padded(&mut bytes, &self.primary_header().serialize(), sector_size);
+        append_padded(&mut bytes, &self.serialize_partitions(), sector_size);
+        append_padded(&mut bytes, &self.serialize_backup_partitions(), sector_size);
+        append_padded(&mut bytes, &self.backup_header().serialize(), sector_size);
+        bytes
+    }
+
+    /// Every structure as it goes on disk, `(lba, bytes)` with the bytes zero padded to
+    /// whole blocks: protective MBR, primary header and array, backup array and header.
+    /// Written as is, the CRCs are not recomputed.
+    pub(crate) fn disk_writes(&self, block_size: usize) -> Vec<(u64, Vec<u8>)> {
+        let primary = self.primary_header();
+        let backup = self.backup_header();
+        let padded = |data: &[u8]| {
+            let mut bytes = Vec::new();
+            append_padded(&mut bytes, data, block_size);
+            bytes
+        };
+        vec![
+            (
+                PROTECTIVE_MBR_LBA as u64,
+                padded(&self.protective_mbr().serialize()),
+            ),
+            (primary.my_lba, padded(&primary.serialize())),
+            (primary.part_start, padded(&self.serialize_partitions())),
+            (
+                backup.part_start,
+                padded(&self.serialize_backup_partitions()),
+            ),
+            (backup.my_lba, padded(&backup.serialize())),
+        ]
+    }
+
+    /// Recompute `crc32_parts` from the partition arrays and then `crc32` of both headers.
+    pub fn recompute_crc32(&mut self) {
+        let crc32_parts = crc::crc32(&self.serialize_partitions());
+        let header = self.primary_header_mut();
+        header.crc32_parts = crc32_parts;
+        header.crc32 = header.calculate_crc32();
+        let crc32_parts = crc::crc32(&self.serialize_backup_partitions());
+        let header = self.backup_header_mut();
+        header.crc32_parts = crc32_parts;
+        header.crc32 = header.calculate_crc32();
+    }
+
+    /// The disk GUID of the primary header.
+    pub fn disk_guid(&self) -> Uuid {
+        self.primary_header().disk_guid
+    }
+
+    /// Set the disk GUID of both headers and recompute the CRCs, e.g. to give a cloned
+    /// image an identity of its own.
+    pub fn set_disk_guid(&mut self, guid: Uuid) {
+        self.primary_header_mut().disk_guid = guid;
+        self.backup_header_mut().disk_guid = guid;
+        self.recompute_crc32();
+    }
+
+    /// Give every primary partition with a nil `part_guid` a fresh random GUID from `rng`,
+    /// mirrored onto the backup entry of the same slot if it is nil too, then recompute the
+    /// CRCs. Partitions that already have a GUID are left alone.
+    pub fn assign_missing_guids(&mut self, rng: &mut impl FnMut() -> u64) {
+        for (part, index) in self.partitions.iter_mut() {
+            if !part.part_guid.is_nil() {
+                continue;
+            }
+            part.part_guid = Uuid::new_v4(rng);
+            if let Some((backup, _)) = self
+                .backup_partitions
+                .iter_mut()
+                .find(|(backup, backup_index)| backup_index == index && backup.part_guid.is_nil())
+            {
+                backup.part_guid = part.part_guid;
             }
         }
-        if find_index != PARTITION_LBA_SIZE {
-            Some(&mut self.partitions.get_mut(find_index).unwrap().0)
-        } else {
-            None
+        self.recompute_crc32();
+    }
+
+    /// Make the backup partition array a copy of the primary one and refresh the backup
+    /// header's CRCs, after editing partitions through [`GptLayout::partition_mut`]. The
+    /// layout level mutators like [`GptLayout::add_partition`] keep both arrays in sync on
+    /// their own.
+    pub fn sync_backup(&mut self) {
+        self.copy_partitions_to_backup();
+        let crc32_parts = crc::crc32(&self.serialize_backup_partitions());
+        let header = self.backup_header_mut();
+        header.crc32_parts = crc32_parts;
+        header.crc32 = header.calculate_crc32();
+    }
+
+    /// Zero every ghost entry in both partition arrays, so they serialize to all zero
+    /// bytes and the CRCs only depend on the live partitions, then recompute the CRCs.
+    pub fn clear_ghosts(&mut self) {
+        self.ghosts.clear();
+        self.backup_ghosts.clear();
+        self.recompute_crc32();
+    }
+}
+
+impl GptLayout {
+    /// Put `part` in the first free slot of both partition arrays and recompute the CRCs,
+    /// returning the one-based slot. Fails with [`LayoutError::PartitionTableFull`] when all
+    /// `num_parts` slots are taken, and like [`GptLayout::resize_partition`] when `part` has
+    /// a nil type, ends before it starts, leaves the usable range or overlaps another
+    /// partition. The `reserved` bytes of `part` are dropped, a new entry is zero past its
+    /// first 128 bytes up to `part_size` as the spec asks.
+    pub fn add_partition(&mut self, mut part: Partition) -> Result<usize, LayoutError> {
+        if part.part_type_guid.is_nil() {
+            return Err(LayoutError::MissingTypeGuid);
         }
+        let num_parts = self.primary_header().num_parts as usize;
+        let index = (1..=num_parts)
+            .find(|index| self.partition(*index).is_none())
+            .ok_or(LayoutError::PartitionTableFull)?;
+        self.check_placement(index, part.start_lba, part.end_lba)?;
+        // a ghost left in the slot would be serialized over the new entry
+        self.ghosts.retain(|(_, slot)| *slot != index);
+        self.backup_ghosts.retain(|(_, slot)| *slot != index);
+        self.backup_partitions.retain(|(_, slot)| *slot != index);
+        part.reserved.clear();
+        self.backup_partitions.push((part.clone(), index));
+        self.partitions.push((part, index));
+        self.reindex();
+        self.recompute_crc32();
+        Ok(index)
     }
-    pub fn backup_partition_mut(&mut self, part_index: usize) -> Option<&mut Partition> {
-        assert!(part_index < MIN_PARTITION_NUM);
-        let mut find_index = PARTITION_LBA_SIZE;
-        for (i, (_, part_i)) in self.backup_partitions.iter().enumerate() {
-            if *part_i == part_index {
-                find_index = i;
-                break;
+
+    /// Remove the partition in the one-based slot `index` from both partition arrays and
+    /// recompute the CRCs, returning the primary entry. GPT slots are positional, the other
+    /// partitions keep their indices and the slot is free for [`GptLayout::add_partition`].
+    pub fn remove_partition(&mut self, index: usize) -> Option<Partition> {
+        let position = self
+            .partitions
+            .iter()
+            .position(|(_, slot)| *slot == index)?;
+        let (part, _) = self.partitions.remove(position);
+        self.backup_partitions.retain(|(_, slot)| *slot != index);
+        self.reindex();
+        self.recompute_crc32();
+        Some(part)
+    }
+
+    /// Zero slot `index` in both arrays, whether it holds a partition or a ghost entry, and
+    /// recompute the CRCs. The other partitions keep their slot numbers. Returns whether
+    /// there was anything to clear.
+    pub fn clear_slot(&mut self, index: usize) -> bool {
+        let mut cleared = false;
+        for table in [
+            &mut self.partitions,
+            &mut self.backup_partitions,
+            &mut self.ghosts,
+            &mut self.backup_ghosts,
+        ] {
+            let len = table.len();
+            table.retain(|(_, slot)| *slot != index);
+            cleared |= table.len() != len;
+        }
+        if cleared {
+            self.reindex();
+            self.recompute_crc32();
+        }
+        cleared
+    }
+
+    /// Move the end of the partition in slot `index` to `new_end_lba` in both arrays and
+    /// recompute the CRCs. The partition must still start before it ends, stay within
+    /// `last_usable` and not run into another partition, otherwise nothing changes.
+    pub fn resize_partition(&mut self, index: usize, new_end_lba: u64) -> Result<(), LayoutError> {
+        let start = self
+            .partition(index)
+            .ok_or(LayoutError::PartitionNotFound(index))?
+            .start_lba;
+        self.check_placement(index, start, new_end_lba)?;
+        for (part, slot) in self
+            .partitions
+            .iter_mut()
+            .chain(self.backup_partitions.iter_mut())
+        {
+            if *slot == index {
+                part.end_lba = new_end_lba;
             }
         }
-        if find_index != PARTITION_LBA_SIZE {
-            Some(&mut self.backup_partitions.get_mut(find_index).unwrap().0)
-        } else {
-            None
+        self.recompute_crc32();
+        Ok(())
+    }
+
+    /// Check a partition in slot `index` could span `start..=end`: it must not end before it
+    /// starts, must lie within the usable range and must not overlap the partitions in the
+    /// other slots.
+    fn check_placement(&self, index: usize, start: u64, end: u64) -> Result<(), LayoutError> {
+        if end < start {
+            return Err(LayoutError::InvalidPartitionRange(index));
         }
+        let header = self.primary_header();
+        if start < header.first_usable || end > header.last_usable {
+            return Err(LayoutError::PartitionOutOfBounds { index, start, end });
+        }
+        if let Some((_, other)) = self
+            .partitions
+            .iter()
+            .find(|(part, slot)| *slot != index && part.start_lba <= end && start <= part.end_lba)
+        {
+            return Err(LayoutError::PartitionsOverlap(index, *other));
+        }
+        Ok(())
     }
-    pub fn backup_header_mut(&mut self) -> &mut Header {
-        unsafe { self.backup_header.as_mut().assume_init_mut() }
+
+    /// Order the primary partitions by `start_lba`, each keeping its one-based slot, only
+    /// the order of [`GptLayout::partitions`] changes.
+    pub fn sort_partitions_by_start(&mut self) {
+        self.partitions.sort_by_key(|(part, _)| part.start_lba);
+        self.reindex();
+    }
+
+    /// Rebuild the slot indices after partitions were added or removed.
+    fn reindex(&mut self) {
+        self.slots = index_slots(&self.partitions);
+        self.backup_slots = index_slots(&self.backup_partitions);
+    }
+}
+
+impl GptLayout {
+    /// Rebuild the primary header and partition array from the backup, the usual repair when
+    /// the start of the disk got damaged. The primary header is the backup with `my_lba` and
+    /// `backup_lba` swapped and the array at LBA 2, both CRCs get recomputed.
+    pub fn restore_primary_from_backup(&mut self) -> Result<(), LayoutError> {
+        if !self.backup_header().crc32_valid() {
+            return Err(LayoutError::NoValidBackup);
+        }
+        self.copy_backup_to_primary();
+        self.recompute_crc32();
+        Ok(())
+    }
+
+    /// Overwrite the primary header and array with the backup ones, without touching the
+    /// CRCs.
+    fn copy_backup_to_primary(&mut self) {
+        let backup = self.backup_header();
+        let mut primary = backup.clone();
+        primary.my_lba = backup.backup_lba;
+        primary.backup_lba = backup.my_lba;
+        primary.part_start = PRIMARY_HEADER_LBA as u64 + 1;
+        self.set_primary_header(primary);
+        self.partitions = self.backup_partitions.clone();
+        self.slots = self.backup_slots.clone();
+        self.ghosts = self.backup_ghosts.clone();
+    }
+
+    /// Overwrite the backup header and array with the primary ones, without touching the
+    /// CRCs. The backup header goes to the last LBA of the `disk_lba_count` LBA disk, not
+    /// where the primary says, and the backup array right in front of it.
+    fn copy_primary_to_backup(&mut self, disk_lba_count: u64, block_size: usize) {
+        let backup = self
+            .primary_header()
+            .as_backup(disk_lba_count, block_size as u64);
+        self.set_backup_header(backup);
+        self.copy_partitions_to_backup();
+    }
+
+    fn copy_partitions_to_backup(&mut self) {
+        self.backup_partitions = self.partitions.clone();
+        self.backup_slots = self.slots.clone();
+        self.backup_ghosts = self.ghosts.clone();
+    }
+}
+
+fn same_slots(a: &[(Partition, usize)], b: &[(Partition, usize)]) -> bool {
+    a.len() == b.len() && a.iter().all(|entry| b.contains(entry))
+}
+
+/// Map every one-based slot to the position of its entry in `partitions`, so slot lookups
+/// don't have to scan the vec. Has to be rebuilt whenever entries are added or removed.
+fn index_slots(partitions: &[(Partition, usize)]) -> Vec<Option<usize>> {
+    let len = partitions
+        .iter()
+        .map(|(_, index)| index + 1)
+        .max()
+        .unwrap_or(0);
+    let mut slots = vec![None; len];
+    for (position, (_, index)) in partitions.iter().enumerate() {
+        slots[*index].get_or_insert(position);
     }
+    slots
+}
+
+/// Append `data` to `bytes`, zero padded to a multiple of `block_size`.
+fn append_padded(bytes: &mut Vec<u8>, data: &[u8], block_size: usize) {
+    bytes.extend_from_slice(data);
+    bytes.resize(
+        bytes.len() + data.len().next_multiple_of(block_size) - data.len(),
+        0,
+    );
+}
+
+fn serialize_table(
+    partitions: &[(Partition, usize)],
+    ghosts: &[(Partition, usize)],
+    num_parts: usize,
+    part_size: usize,
+) -> Vec<u8> {
+    let part_size = part_size.max(PARTITION_LBA_SIZE);
+    let mut bytes = vec![0u8; num_parts * part_size];
+    for (part, index) in partitions.iter().chain(ghosts) {
+        if (1..=num_parts).contains(index) {
+            let start = (index - 1) * part_size;
+            bytes[start..start + part_size].copy_from_slice(&part.serialize_entry(part_size));
+        }
+    }
+    bytes
+}
+
+fn decode_table(
+    array_bytes: &[u8],
+    num_parts: u32,
+    part_size: u32,
+    partitions: &mut Vec<(Partition, usize)>,
+    ghosts: &mut Vec<(Partition, usize)>,
+) -> Result<(), PartitionError> {
+    let part_size = part_size as usize;
+    if part_size < PARTITION_LBA_SIZE {
+        return Err(PartitionError::InvalidEntryLength);
+    }
+    array_bytes
+        .chunks_exact(part_size)
+        .take(num_parts as usize)
+        .enumerate()
+        .try_for_each(|(index, entry)| decode_entry(entry, index + 1, partitions, ghosts))
+}
+
+fn decode_entry(
+    entry: &[u8],
+    index: usize,
+    partitions: &mut Vec<(Partition, usize)>,
+    ghosts: &mut Vec<(Partition, usize)>,
+) -> Result<(), PartitionError> {
+    match Partition::deserialize(entry)? {
+        Some(part) => partitions.push((part, index)),
+        None if is_ghost(entry) => ghosts.push((Partition::deserialize_unchecked(entry)?, index)),
+        None => {}
+    }
+    Ok(())
+}
+
+/// An entry is a ghost when its type GUID is nil but some other byte is not zero.
+fn is_ghost(entry: &[u8]) -> bool {
+    entry.iter().any(|byte| *byte != 0)
 }
 
 fn write_to_bytes<const SIZE: usize>(val: u64, bytes: &mut [u8], start: usize) {
@@ -177,9 +991,7 @@ fn write_to_bytes<const SIZE: usize>(val: u64, bytes: &mut [u8], start: usize) {
         8 => LittleEndian::write_u64(&mut bts, val),
         _ => {}
     }
-    for index in 0..SIZE {
-        bytes[start + index] = bts[index];
-    }
+    bytes[start..start + SIZE].copy_from_slice(&bts);
 }
 
 fn copy_bytes<T: ToU8>(src: &[T], dst: &mut [u8], start: usize, size: usize) {
@@ -190,14 +1002,533 @@ trait ToU8 {
     fn as_u8(&self) -> u8;
 }
 
-impl ToU8 for char {
+impl ToU8 for u8 {
     fn as_u8(&self) -> u8 {
-        *self as u8
+        *self
     }
 }
 
-impl ToU8 for u8 {
-    fn as_u8(&self) -> u8 {
-        *self
+/// Fixtures shared by the unit tests of every module.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Blocks of the test disk.
+    pub(crate) const DISK_LBAS: u64 = 8192;
+
+    pub(crate) fn part(type_guid: Uuid, guid: u8, start_lba: u64, end_lba: u64) -> Partition {
+        PartitionBuilder::new()
+            .type_guid(type_guid)
+            .guid(Uuid::from_bytes([guid; 16]))
+            .start_lba(start_lba)
+            .end_lba(end_lba)
+            .build()
+            .unwrap()
+    }
+
+    /// A 4 MiB disk of 512 byte blocks with the standard 128 entry tables and three
+    /// partitions: an ESP in slot 1 and two Linux filesystems in slots 2 and 3.
+    pub(crate) fn layout() -> GptLayout {
+        let disk_guid = Uuid::from_bytes([0x12; 16]);
+        let primary = Header::new_primary(disk_guid, DISK_LBAS, 512).unwrap();
+        let mut layout = GptLayout::new();
+        layout.set_protective_mbr(ProtectiveMBR::new(DISK_LBAS));
+        layout.set_backup_header(primary.as_backup(DISK_LBAS, 512));
+        layout.set_primary_header(primary);
+        for part in [
+            part(part_types::EFI_SYSTEM, 1, 2048, 4095),
+            part(part_types::LINUX_FILESYSTEM, 2, 4096, 6143),
+            part(part_types::LINUX_FILESYSTEM, 3, 6144, 8000),
+        ] {
+            layout.add_partition(part).unwrap();
+        }
+        layout
+    }
+
+    /// [`layout`] written to a disk image.
+    pub(crate) fn disk() -> Vec<u8> {
+        let mut disk = io::MemDisk::new(DISK_LBAS, 512).unwrap();
+        layout().store(&mut disk).unwrap();
+        disk.as_slice().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_partition_keeps_the_other_slots() {
+        let mut layout = testing::layout();
+        let middle = layout.remove_partition(2).unwrap();
+        assert_eq!(middle.start_lba, 4096);
+        assert!(layout.partition(2).is_none());
+        assert!(layout.backup_partition(2).is_none());
+        assert_eq!(layout.partition(1).unwrap().start_lba, 2048);
+        assert_eq!(layout.partition(3).unwrap().start_lba, 6144);
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_eq!(layout.add_partition(middle).unwrap(), 2);
+    }
+
+    #[test]
+    fn add_partition_rejects_bad_placements() {
+        let mut layout = testing::layout();
+        let last_usable = layout.primary_header().last_usable;
+        let mut nil_type = testing::part(part_types::LINUX_FILESYSTEM, 4, 8001, 8100);
+        nil_type.part_type_guid = Uuid::NIL;
+        assert!(matches!(
+            layout.add_partition(nil_type),
+            Err(LayoutError::MissingTypeGuid)
+        ));
+        let mut backwards = testing::part(part_types::LINUX_FILESYSTEM, 4, 8001, 8100);
+        backwards.end_lba = 8000;
+        assert!(matches!(
+            layout.add_partition(backwards),
+            Err(LayoutError::InvalidPartitionRange(4))
+        ));
+        let before_usable = testing::part(part_types::LINUX_FILESYSTEM, 4, 2, 100);
+        assert!(matches!(
+            layout.add_partition(before_usable),
+            Err(LayoutError::PartitionOutOfBounds { index: 4, .. })
+        ));
+        let past_usable = testing::part(part_types::LINUX_FILESYSTEM, 4, 8001, last_usable + 1);
+        assert!(matches!(
+            layout.add_partition(past_usable),
+            Err(LayoutError::PartitionOutOfBounds { index: 4, .. })
+        ));
+        let overlapping = testing::part(part_types::LINUX_FILESYSTEM, 4, 7000, 8100);
+        assert!(matches!(
+            layout.add_partition(overlapping),
+            Err(LayoutError::PartitionsOverlap(4, 3))
+        ));
+        assert_eq!(layout.partitions().len(), 3);
+        let fits = testing::part(part_types::LINUX_FILESYSTEM, 4, 8001, last_usable);
+        assert_eq!(layout.add_partition(fits).unwrap(), 4);
+    }
+
+    /// [`testing::layout`] with a deleted but not zeroed entry in slot 6.
+    fn ghosted_layout() -> GptLayout {
+        let mut layout = testing::layout();
+        let mut blk = vec![0u8; 512];
+        // end_lba of the second entry of the block holding slots 5 to 8
+        blk[128 + 40] = 5;
+        layout.init_partitions(&blk, 2).unwrap();
+        layout
+    }
+
+    #[test]
+    fn ghost_entries_reports_deleted_entries() {
+        assert!(testing::layout().ghost_entries().is_empty());
+        let layout = ghosted_layout();
+        assert_eq!(layout.ghost_entries(), [6]);
+        assert!(layout.partition(6).is_none());
+        assert_eq!(layout.partition_count(), 3);
+    }
+
+    #[test]
+    fn clear_ghosts_zeroes_the_slot() {
+        let mut layout = ghosted_layout();
+        layout.recompute_crc32();
+        let ghosted_crc32_parts = layout.primary_header().crc32_parts;
+        let ghost = 5 * 128..6 * 128;
+        assert!(layout.serialize_partitions()[ghost.clone()]
+            .iter()
+            .any(|b| *b != 0));
+        layout.clear_ghosts();
+        assert!(layout.ghost_entries().is_empty());
+        assert!(layout.serialize_partitions()[ghost].iter().all(|b| *b == 0));
+        assert_eq!(layout.partition_count(), 3);
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_ne!(layout.primary_header().crc32_parts, ghosted_crc32_parts);
+        assert_eq!(
+            layout.primary_header().crc32_parts,
+            testing::layout().primary_header().crc32_parts
+        );
+    }
+
+    #[test]
+    fn metadata_digest_input_changes_with_a_partition() {
+        let mut layout = testing::layout();
+        let digest = layout.metadata_digest_input(512);
+        // MBR, two headers and two 32 sector arrays
+        assert_eq!(digest.len(), 512 * (3 + 64));
+        assert_eq!(digest, layout.metadata_digest_input(512));
+        layout.partition_mut(1).unwrap().end_lba += 1;
+        assert_ne!(digest, layout.metadata_digest_input(512));
+    }
+
+    #[test]
+    fn is_last_partition_is_the_highest_end() {
+        let layout = testing::layout();
+        assert!(layout.is_last_partition(3));
+        assert!(!layout.is_last_partition(1));
+        assert!(!layout.is_last_partition(2));
+        // an empty slot
+        assert!(!layout.is_last_partition(4));
+    }
+
+    #[test]
+    fn assign_missing_guids_fills_only_nil_guids() {
+        let mut layout = testing::layout();
+        let kept = layout.partition(1).unwrap().part_guid;
+        layout.partition_mut(2).unwrap().part_guid = Uuid::NIL;
+        layout.backup_partition_mut(2).unwrap().part_guid = Uuid::NIL;
+        let mut state = 0x1234_5678_9abc_def0u64;
+        layout.assign_missing_guids(&mut || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            state
+        });
+        assert_eq!(layout.partition(1).unwrap().part_guid, kept);
+        let assigned = layout.partition(2).unwrap().part_guid;
+        assert!(!assigned.is_nil());
+        assert_eq!(layout.backup_partition(2).unwrap().part_guid, assigned);
+        assert_eq!(layout.valid_headers(), (true, true));
+    }
+
+    #[test]
+    fn clone_and_eq_compare_the_whole_layout() {
+        let layout = testing::layout();
+        let mut copy = layout.clone();
+        assert_eq!(copy, layout);
+        copy.partition_mut(1).unwrap().attrs = 1;
+        assert_ne!(copy, layout);
+        assert_eq!(GptLayout::new(), GptLayout::new().clone());
+        assert_ne!(GptLayout::new(), layout);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_through_json() {
+        let layout = testing::layout();
+        let part = layout.partition(1).unwrap();
+        let json = serde_json::to_string(part).unwrap();
+        assert!(
+            json.contains("\"part_type_guid\":\"c12a7328-f81f-11d2-ba4b-00a0c93ec93b\""),
+            "{json}"
+        );
+        assert_eq!(&serde_json::from_str::<Partition>(&json).unwrap(), part);
+        let name = PartitionName::new("boot 😀").unwrap();
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"boot 😀\"");
+        let header = serde_json::to_string(layout.primary_header()).unwrap();
+        assert_eq!(
+            &serde_json::from_str::<Header>(&header).unwrap(),
+            layout.primary_header()
+        );
+        let mbr = serde_json::to_string(layout.protective_mbr()).unwrap();
+        assert_eq!(
+            &serde_json::from_str::<ProtectiveMBR>(&mbr).unwrap(),
+            layout.protective_mbr()
+        );
+    }
+
+    #[test]
+    fn sort_partitions_by_start_keeps_the_slots() {
+        let mut layout = testing::layout();
+        let esp = layout.remove_partition(1).unwrap();
+        let slot3 = layout.partition_mut(3).unwrap();
+        slot3.start_lba = 100;
+        slot3.end_lba = 2000;
+        layout.add_partition(esp).unwrap();
+        let order = |layout: &GptLayout| {
+            layout
+                .partitions()
+                .iter()
+                .map(|(part, index)| (*index, part.start_lba))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&layout), [(2, 4096), (3, 100), (1, 2048)]);
+        layout.sort_partitions_by_start();
+        assert_eq!(order(&layout), [(3, 100), (1, 2048), (2, 4096)]);
+        assert_eq!(layout.partition(1).unwrap().start_lba, 2048);
+        assert_eq!(layout.partition(3).unwrap().start_lba, 100);
+    }
+
+    #[test]
+    fn sync_backup_follows_partition_mut() {
+        let mut layout = testing::layout();
+        layout.partition_mut(3).unwrap().end_lba = 7000;
+        layout.recompute_crc32();
+        assert!(!layout.backup_diff().unwrap().is_empty());
+        assert_eq!(layout.valid_headers(), (true, true));
+
+        layout.sync_backup();
+        assert!(layout.backup_diff().unwrap().is_empty());
+        assert!(layout.primary_matches_backup());
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_eq!(
+            layout.serialize_partitions(),
+            layout.serialize_backup_partitions()
+        );
+    }
+
+    #[test]
+    fn short_entry_size_does_not_panic_when_serializing() {
+        let mut layout = testing::layout();
+        layout.primary_header_mut().part_size = 64;
+        let table = layout.serialize_partitions();
+        assert_eq!(table.len(), 128 * PARTITION_LBA_SIZE);
+    }
+
+    #[test]
+    fn tables_fit_rejects_an_overflowing_array_start() {
+        let mut layout = testing::layout();
+        layout.primary_header_mut().part_start = u64::MAX;
+        layout.backup_header_mut().part_start = u64::MAX;
+        assert_eq!(layout.tables_fit(512), (false, false));
+    }
+
+    #[test]
+    fn restore_primary_from_backup_repairs_a_damaged_start() {
+        let mut layout = testing::layout();
+        let header = layout.primary_header_mut();
+        header.my_lba = 7;
+        header.part_start = 40;
+        header.crc32 ^= 1;
+        layout.partition_mut(2).unwrap().end_lba = 0;
+        assert_eq!(layout.valid_headers(), (false, true));
+
+        layout.restore_primary_from_backup().unwrap();
+        assert_eq!(layout.valid_headers(), (true, true));
+        let (primary, backup) = (layout.primary_header(), layout.backup_header());
+        assert_eq!(primary.my_lba, backup.backup_lba);
+        assert_eq!(primary.backup_lba, backup.my_lba);
+        assert_eq!(
+            (primary.my_lba, primary.backup_lba, primary.part_start),
+            (1, testing::DISK_LBAS - 1, 2)
+        );
+        assert_eq!(layout.partition(2).unwrap().end_lba, 6143);
+        assert_eq!(layout, testing::layout());
+    }
+
+    #[test]
+    fn restore_primary_from_backup_needs_a_valid_backup() {
+        let mut layout = testing::layout();
+        layout.backup_header_mut().crc32 ^= 1;
+        layout.primary_header_mut().first_usable = 0;
+        assert!(matches!(
+            layout.restore_primary_from_backup(),
+            Err(LayoutError::NoValidBackup)
+        ));
+        assert_eq!(layout.primary_header().first_usable, 0);
+    }
+
+    /// The errors `validate` reports after `damage` was done to the test layout.
+    fn validate_after(damage: impl FnOnce(&mut GptLayout)) -> Vec<LayoutError> {
+        let mut layout = testing::layout();
+        damage(&mut layout);
+        layout.validate(testing::DISK_LBAS).unwrap_err()
+    }
+
+    #[test]
+    fn validate_accepts_a_clean_layout() {
+        assert!(testing::layout().validate(testing::DISK_LBAS).is_ok());
+    }
+
+    #[test]
+    fn validate_reports_each_problem() {
+        let errors = validate_after(|layout| layout.set_protective_mbr(ProtectiveMBR::new(100)));
+        assert!(matches!(
+            errors[..],
+            [LayoutError::InvalidProtectiveMbr(
+                MBRError::InvalidProtectiveLayout
+            )]
+        ));
+
+        let errors = validate_after(|layout| {
+            layout.primary_header_mut().my_lba = 2;
+            layout.recompute_crc32();
+        });
+        assert!(matches!(
+            errors[..],
+            [
+                LayoutError::InvalidHeader {
+                    header: HeaderKind::Primary,
+                    error: HeaderError::InvalidMyLba,
+                },
+                LayoutError::HeadersMismatch,
+            ]
+        ));
+
+        let errors = validate_after(|layout| {
+            layout.backup_header_mut().part_start = testing::DISK_LBAS - 1;
+            layout.recompute_crc32();
+        });
+        assert!(matches!(
+            errors[..],
+            [LayoutError::InvalidHeader {
+                header: HeaderKind::Backup,
+                error: HeaderError::InvalidPartitionArrayLba,
+            }]
+        ));
+
+        let errors = validate_after(|layout| layout.backup_header_mut().crc32 ^= 1);
+        assert!(matches!(
+            errors[..],
+            [LayoutError::HeaderCrcMismatch(HeaderKind::Backup)]
+        ));
+
+        let errors = validate_after(|layout| layout.partition_mut(3).unwrap().end_lba = 7000);
+        assert!(matches!(
+            errors[..],
+            [LayoutError::PartitionTableCrcMismatch(HeaderKind::Primary)]
+        ));
+
+        let errors = validate_after(|layout| {
+            layout.backup_header_mut().disk_guid = Uuid::from_bytes([0x34; 16]);
+            layout.recompute_crc32();
+        });
+        assert!(matches!(errors[..], [LayoutError::HeadersMismatch]));
+
+        let damage_partition = |slot, edit: fn(&mut Partition)| {
+            validate_after(move |layout| {
+                edit(layout.partition_mut(slot).unwrap());
+                layout.sync_backup();
+                layout.recompute_crc32();
+            })
+        };
+        let errors = damage_partition(2, |part| part.start_lba = 4000);
+        assert!(matches!(errors[..], [LayoutError::PartitionsOverlap(1, 2)]));
+
+        let errors = damage_partition(3, |part| part.part_guid = Uuid::from_bytes([2; 16]));
+        assert!(matches!(
+            errors[..],
+            [LayoutError::DuplicatePartitionGuid(2, 3)]
+        ));
+
+        let errors = damage_partition(1, |part| part.start_lba = 10);
+        assert!(matches!(
+            errors[..],
+            [
+                LayoutError::OverlapsMetadata(1),
+                LayoutError::PartitionOutOfBounds {
+                    index: 1,
+                    start: 10,
+                    end: 4095,
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn find_duplicate_guids_skips_nil_guids() {
+        let mut layout = testing::layout();
+        assert!(layout.find_duplicate_guids().is_empty());
+        layout.partition_mut(3).unwrap().part_guid = Uuid::from_bytes([1; 16]);
+        assert_eq!(layout.find_duplicate_guids(), [(1, 3)]);
+        for slot in [1, 3] {
+            layout.partition_mut(slot).unwrap().part_guid = Uuid::default();
+        }
+        assert!(layout.find_duplicate_guids().is_empty());
+    }
+
+    #[test]
+    fn validate_partition_bounds_checks_the_usable_range() {
+        let mut layout = testing::layout();
+        assert!(layout.validate_partition_bounds().is_empty());
+        let last_usable = layout.primary_header().last_usable;
+        layout.partition_mut(3).unwrap().end_lba = last_usable;
+        assert!(layout.validate_partition_bounds().is_empty());
+        layout.partition_mut(3).unwrap().end_lba = last_usable + 1;
+        layout.partition_mut(1).unwrap().start_lba = layout.primary_header().first_usable - 1;
+        assert_eq!(layout.validate_partition_bounds(), [1, 3]);
+    }
+
+    #[test]
+    fn find_metadata_overlaps_ignores_the_usable_range() {
+        let mut layout = testing::layout();
+        assert!(layout.find_metadata_overlaps().is_empty());
+        // a header claiming the whole disk is usable doesn't make the backup array free
+        layout.primary_header_mut().last_usable = testing::DISK_LBAS - 1;
+        layout.partition_mut(3).unwrap().end_lba = testing::DISK_LBAS - 20;
+        assert!(layout.validate_partition_bounds().is_empty());
+        assert_eq!(layout.find_metadata_overlaps(), [3]);
+        layout.partition_mut(1).unwrap().start_lba = 1;
+        assert_eq!(layout.find_metadata_overlaps(), [1, 3]);
+    }
+
+    #[test]
+    fn resize_partition_rejects_bad_ends() {
+        let mut layout = testing::layout();
+        let last_usable = layout.primary_header().last_usable;
+        assert!(matches!(
+            layout.resize_partition(1, 4096),
+            Err(LayoutError::PartitionsOverlap(1, 2))
+        ));
+        assert!(matches!(
+            layout.resize_partition(3, last_usable + 1),
+            Err(LayoutError::PartitionOutOfBounds { index: 3, .. })
+        ));
+        assert!(matches!(
+            layout.resize_partition(2, 4095),
+            Err(LayoutError::InvalidPartitionRange(2))
+        ));
+        assert!(matches!(
+            layout.resize_partition(4, 9000),
+            Err(LayoutError::PartitionNotFound(4))
+        ));
+        assert_eq!(layout, testing::layout());
+    }
+
+    #[test]
+    fn resize_partition_updates_both_arrays() {
+        let mut layout = testing::layout();
+        let last_usable = layout.primary_header().last_usable;
+        layout.resize_partition(3, last_usable).unwrap();
+        assert_eq!(layout.partition(3).unwrap().end_lba, last_usable);
+        assert_eq!(layout.backup_partition(3).unwrap().end_lba, last_usable);
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_ne!(
+            layout.primary_header().crc32_parts,
+            testing::layout().primary_header().crc32_parts
+        );
+        layout.resize_partition(3, 6144).unwrap();
+        assert_eq!(layout.partition(3).unwrap().size_lba(), 1);
+        assert!(layout.validate(testing::DISK_LBAS).is_ok());
+    }
+
+    #[test]
+    fn clear_slot_zeroes_partitions_and_ghosts() {
+        let mut layout = testing::layout();
+        assert!(layout.clear_slot(2));
+        assert!(layout.partition(2).is_none());
+        assert!(layout.backup_partition(2).is_none());
+        assert_eq!(layout.partition(3).unwrap().start_lba, 6144);
+        assert!(layout.serialize_partitions()[128..256]
+            .iter()
+            .all(|b| *b == 0));
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_eq!(
+            layout.serialize_partitions(),
+            layout.serialize_backup_partitions()
+        );
+        let crc32_parts = layout.primary_header().crc32_parts;
+        assert!(!layout.clear_slot(2));
+        assert_eq!(layout.primary_header().crc32_parts, crc32_parts);
+
+        let mut layout = ghosted_layout();
+        layout.recompute_crc32();
+        assert!(layout.clear_slot(6));
+        assert!(layout.ghost_entries().is_empty());
+        assert_eq!(layout.partition_count(), 3);
+        assert_eq!(layout.valid_headers(), (true, true));
+        assert_eq!(
+            layout.primary_header().crc32_parts,
+            testing::layout().primary_header().crc32_parts
+        );
+    }
+
+    #[test]
+    fn init_partitions_needs_the_header() {
+        let mut layout = GptLayout::new();
+        let blk = [0u8; 512];
+        assert!(matches!(
+            layout.init_partitions(&blk, 1),
+            Err(PartitionError::MissingHeader)
+        ));
+        assert!(matches!(
+            layout.init_backup_partitions(&blk, 1),
+            Err(PartitionError::MissingHeader)
+        ));
     }
 }
@@ -2,12 +2,14 @@ use core::fmt;
 
 use byteorder::{ByteOrder, LittleEndian};
 
-use super::{copy_bytes, write_to_bytes, MBRError};
+use super::{check_signature, copy_bytes, crc, write_to_bytes, Header, MBRError, Partition};
 
 pub const PROTECTIVE_MBR_LBA: usize = 0;
 pub const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtectiveMBR {
+    #[cfg_attr(feature = "serde", serde(with = "bootcode"))]
     pub bootcode: [u8; 440],
     pub disk_signature: [u8; 4],
     pub unknown: u16,
@@ -27,18 +29,72 @@ impl Default for ProtectiveMBR {
     }
 }
 
+/// The bootcode is summed up by its length and CRC32 to keep the output short.
 impl fmt::Debug for ProtectiveMBR {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Protective MBR, partitions: {:#?}", self.partitions)
+        f.debug_struct("ProtectiveMBR")
+            .field(
+                "bootcode",
+                &format_args!(
+                    "[{} bytes, crc={:#010x}]",
+                    self.bootcode.len(),
+                    crc::crc32(&self.bootcode)
+                ),
+            )
+            .field(
+                "disk_signature",
+                &format_args!("{:02x?}", self.disk_signature),
+            )
+            .field("unknown", &format_args!("{:#06x}", self.unknown))
+            .field("partitions", &self.partitions)
+            .field("signature", &format_args!("{:02x?}", self.signature))
+            .finish()
     }
 }
 
 impl ProtectiveMBR {
+    /// A protective MBR for a disk of `disk_lba_count` LBAs: no bootcode, a single 0xEE
+    /// record covering LBA 1 to the end of the disk (capped at `0xFFFFFFFF`) and the
+    /// `0x55AA` signature.
+    pub fn new(disk_lba_count: u64) -> Self {
+        let size = disk_lba_count.saturating_sub(1).min(0xFF_FF_FF_FF) as u32;
+        Self {
+            bootcode: [0u8; 440],
+            disk_signature: [0u8; 4],
+            unknown: 0,
+            partitions: [
+                PartRecord::new_protective(Some(size)),
+                PartRecord::zero(),
+                PartRecord::zero(),
+                PartRecord::zero(),
+            ],
+            signature: MBR_SIGNATURE,
+        }
+    }
+
+    /// The protective MBR matching `header` on a disk of `disk_lba_count` LBAs: the 0xEE
+    /// record covers LBA 1 up to the end of the disk (capped at `0xFFFFFFFF` LBAs), see
+    /// [`ProtectiveMBR::new`]. A header whose backup isn't at the last LBA gets logged, the
+    /// record still protects the whole disk.
+    pub fn from_header(header: &Header, disk_lba_count: u64) -> Self {
+        if header.backup_lba != disk_lba_count.saturating_sub(1) {
+            log::warn!(
+                "backup header at LBA {} but the disk ends at LBA {}",
+                header.backup_lba,
+                disk_lba_count.saturating_sub(1)
+            );
+        }
+        Self::new(disk_lba_count)
+    }
+
     pub fn deserialize(blk: &[u8]) -> Result<Self, MBRError> {
+        if blk.len() < 512 {
+            return Err(MBRError::InvalidMBRLength);
+        }
         let mut bootcode = [0u8; 440];
         bootcode.copy_from_slice(&blk[0..440]);
-        let disk_signature = [0u8; 4];
-        bootcode.copy_from_slice(&blk[440..444]);
+        let mut disk_signature = [0u8; 4];
+        disk_signature.copy_from_slice(&blk[440..444]);
         let unknown = LittleEndian::read_u16(&blk[444..446]);
         let partitions = [
             PartRecord::from_bytes(&blk[446..462])?,
@@ -73,10 +129,201 @@ impl ProtectiveMBR {
         copy_bytes(&self.signature, &mut bytes, 510, 2);
         bytes
     }
+
+    /// The partition record at `i`, `None` unless `i` is in `0..4`.
+    pub fn record(&self, i: usize) -> Option<&PartRecord> {
+        self.partitions.get(i)
+    }
+
+    /// The mutable partition record at `i`, `None` unless `i` is in `0..4`.
+    pub fn record_mut(&mut self, i: usize) -> Option<&mut PartRecord> {
+        self.partitions.get_mut(i)
+    }
+
+    /// Whether the MBR is hybrid, i.e. has records besides the 0xEE one. Rewriting such an
+    /// MBR with a purely protective one breaks legacy boot.
+    pub fn is_hybrid(&self) -> bool {
+        self.hybrid_records().next().is_some()
+    }
+
+    /// The records that are neither protective (0xEE) nor unused.
+    pub fn hybrid_records(&self) -> impl Iterator<Item = &PartRecord> {
+        self.partitions
+            .iter()
+            .filter(|part| part.os_type != 0xEE && !part.is_zero())
+    }
+
+    /// Check that the MBR really is protective: exactly one 0xEE record starting at LBA 1
+    /// and covering the rest of the disk (capped at `0xFFFFFFFF`), the other three zeroed.
+    pub fn validate(&self, disk_lba_count: u64) -> Result<(), MBRError> {
+        let expected_size = disk_lba_count.saturating_sub(1).min(0xFF_FF_FF_FF) as u32;
+        let mut protective = 0;
+        for part in self.partitions.iter() {
+            if part.os_type == 0xEE {
+                if part.lb_start != 1 || part.lb_size != expected_size {
+                    return Err(MBRError::InvalidProtectiveLayout);
+                }
+                protective += 1;
+            } else if !part.is_zero() {
+                return Err(MBRError::InvalidProtectiveLayout);
+            }
+        }
+        if protective != 1 {
+            return Err(MBRError::InvalidProtectiveLayout);
+        }
+        Ok(())
+    }
+}
+
+impl ProtectiveMBR {
+    /// Build a hybrid MBR mirroring up to three GPT partitions as real MBR records, given
+    /// as `(partition, os_type)` pairs, after a 0xEE record covering the GPT metadata.
+    ///
+    /// `active` is the position in `parts` of the record legacy BIOS should boot, it gets
+    /// `boot_indicator` 0x80 and every other record 0x00.
+    pub fn build_hybrid_mbr(
+        disk_lba_count: u64,
+        parts: &[(&Partition, u8)],
+        active: Option<usize>,
+    ) -> Result<Self, MBRError> {
+        if active.is_some_and(|index| index >= parts.len()) {
+            return Err(MBRError::InvalidActiveRecord);
+        }
+        let mut mbr = Self::new(disk_lba_count);
+        mbr.make_hybrid(parts)?;
+        if let Some(index) = active {
+            mbr.partitions[index + 1].boot_indicator = 0x80;
+        }
+        Ok(mbr)
+    }
+
+    /// Turn this MBR into a hybrid one: records 1 to 3 mirror the given `(partition,
+    /// os_type)` pairs, with CHS addresses for the default [`ChsGeometry`], and record 0
+    /// becomes a 0xEE record covering everything in front of the first mirrored partition.
+    /// The bootcode is kept, the MBR is left untouched on error.
+    pub fn make_hybrid(&mut self, parts: &[(&Partition, u8)]) -> Result<(), MBRError> {
+        if parts.len() > 3 {
+            return Err(MBRError::TooManyHybridRecords);
+        }
+        let mut records = [PartRecord::zero(); 3];
+        for (record, (part, os_type)) in records.iter_mut().zip(parts) {
+            *record = part
+                .to_mbr_record(*os_type, false)
+                .ok_or(MBRError::HybridOutOfRange)?;
+        }
+        self.partitions[1..].copy_from_slice(&records);
+        // the protective record covers everything in front of the first mirrored partition
+        if let Some(protective_end) = records
+            .iter()
+            .filter(|part| !part.is_zero())
+            .map(|part| part.lb_start)
+            .min()
+        {
+            self.partitions[0] = PartRecord::new_protective(Some(protective_end.saturating_sub(1)));
+        }
+        Ok(())
+    }
+}
+
+/// What partitioning scheme a disk uses, as told by [`probe`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiskKind {
+    /// A protective MBR and a GPT header at LBA 1.
+    Gpt,
+    /// A GPT header at LBA 1 behind an MBR that also has regular records.
+    Hybrid,
+    /// A plain MBR disk: records but no 0xEE one.
+    Mbr,
+    /// No valid MBR, an empty one, or a 0xEE record without a GPT header behind it.
+    Unknown,
+}
+
+/// Tell the scheme of the disk image `disk` with LBAs of `block_size` bytes from the
+/// records of the MBR at LBA 0 and the signature at LBA 1, without parsing any further.
+pub fn probe(disk: &[u8], block_size: usize) -> DiskKind {
+    let Some(mbr) = disk
+        .get(..512)
+        .and_then(|blk| ProtectiveMBR::deserialize(blk).ok())
+    else {
+        return DiskKind::Unknown;
+    };
+    let protective = mbr.partitions.iter().any(|part| part.os_type == 0xEE);
+    let gpt = disk
+        .get(block_size..block_size + 8)
+        .is_some_and(|sig| check_signature(sig).is_ok());
+    match (protective, gpt) {
+        (true, true) if mbr.is_hybrid() => DiskKind::Hybrid,
+        (true, true) => DiskKind::Gpt,
+        (false, _) if mbr.is_hybrid() => DiskKind::Mbr,
+        _ => DiskKind::Unknown,
+    }
+}
+
+/// serde only handles arrays up to 32 elements, the bootcode goes through a byte slice.
+#[cfg(feature = "serde")]
+mod bootcode {
+    use alloc::vec::Vec;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bootcode: &[u8; 440],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bootcode.as_slice().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 440], D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| D::Error::invalid_length(bytes.len(), &"440 bytes"))
+    }
+}
+
+/// The cylinder/head/sector geometry legacy BIOS uses to address the disk, 255 heads of
+/// 63 sectors by default like every modern disk reports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChsGeometry {
+    heads: u32,
+    sectors_per_track: u32,
+}
+
+impl ChsGeometry {
+    /// A geometry of `heads` heads per cylinder, 1 to 255, and `sectors_per_track` sectors
+    /// per track, 1 to 63. `None` outside those ranges, CHS can't address such a disk.
+    pub fn new(heads: u32, sectors_per_track: u32) -> Option<Self> {
+        if !(1..=255).contains(&heads) || !(1..=63).contains(&sectors_per_track) {
+            return None;
+        }
+        Some(Self {
+            heads,
+            sectors_per_track,
+        })
+    }
+
+    /// Heads per cylinder.
+    pub fn heads(&self) -> u32 {
+        self.heads
+    }
+
+    /// Sectors per track.
+    pub fn sectors_per_track(&self) -> u32 {
+        self.sectors_per_track
+    }
+}
+
+impl Default for ChsGeometry {
+    fn default() -> Self {
+        Self {
+            heads: 255,
+            sectors_per_track: 63,
+        }
+    }
 }
 
 /// A partition record, MBR-style.
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PartRecord {
     /// Bit 7 set if partition is active (bootable)
     pub boot_indicator: u8,
@@ -102,8 +349,11 @@ pub struct PartRecord {
 
 impl PartRecord {
     /// Create a protective Partition Record object with a specific disk size (in LB).
-    pub fn new_protective(lb_size: Option<u32>) -> Self {
-        let size = lb_size.unwrap_or(0xFF_FF_FF_FF);
+    pub const fn new_protective(lb_size: Option<u32>) -> Self {
+        let size = match lb_size {
+            Some(size) => size,
+            None => 0xFF_FF_FF_FF,
+        };
         Self {
             boot_indicator: 0x00,
             start_head: 0x00,
@@ -118,8 +368,29 @@ impl PartRecord {
         }
     }
 
+    /// Create a record of `os_type` covering `size_lba` LBAs from `start_lba`, active if
+    /// `bootable`, with its CHS addresses computed for `geometry`. [`ChsGeometry::new`]
+    /// refuses zero heads or sectors, so any `geometry` can address the record.
+    pub fn new(
+        os_type: u8,
+        bootable: bool,
+        start_lba: u32,
+        size_lba: u32,
+        geometry: ChsGeometry,
+    ) -> Self {
+        let mut record = Self {
+            boot_indicator: if bootable { 0x80 } else { 0x00 },
+            os_type,
+            lb_start: start_lba,
+            lb_size: size_lba,
+            ..Self::zero()
+        };
+        record.set_chs_from_lba(start_lba, size_lba, geometry);
+        record
+    }
+
     /// Create an all-zero Partition Record.
-    pub fn zero() -> Self {
+    pub const fn zero() -> Self {
         Self {
             boot_indicator: 0x00,
             start_head: 0x00,
@@ -134,6 +405,32 @@ impl PartRecord {
         }
     }
 
+    /// Fill the start and end CHS addresses for a record covering `size_lba` LBAs from
+    /// `start_lba`. Addresses past the 1024 cylinders CHS can express are clamped to
+    /// `0xFE/0xFF/0xFF` as the convention goes, the LBA fields are left alone.
+    pub fn set_chs_from_lba(&mut self, start_lba: u32, size_lba: u32, geometry: ChsGeometry) {
+        let end_lba = start_lba.saturating_add(size_lba.saturating_sub(1));
+        (self.start_head, self.start_sector, self.start_track) = lba_to_chs(start_lba, geometry);
+        (self.end_head, self.end_sector, self.end_track) = lba_to_chs(end_lba, geometry);
+    }
+
+    /// The LBA of a CHS address as stored in a record, the two high cylinder bits living in
+    /// the top of `sector`. `None` for sector 0, which CHS doesn't have, or a sector or head
+    /// outside `geometry`.
+    pub fn chs_to_lba(head: u8, sector: u8, track: u8, geometry: ChsGeometry) -> Option<u32> {
+        let cylinder = (sector as u32 & 0xC0) << 2 | track as u32;
+        let sector = sector as u32 & 0x3F;
+        if sector == 0 || sector > geometry.sectors_per_track || head as u32 >= geometry.heads {
+            return None;
+        }
+        Some((cylinder * geometry.heads + head as u32) * geometry.sectors_per_track + sector - 1)
+    }
+
+    /// Whether every field of the record is zero, i.e. the record is unused.
+    pub fn is_zero(&self) -> bool {
+        self.to_bytes() == [0u8; 16]
+    }
+
     /// Parse input bytes into a Partition Record.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, MBRError> {
         if bytes.len() != 16 {
@@ -166,7 +463,7 @@ impl PartRecord {
         bytes[4] = self.os_type;
         bytes[5] = self.end_head;
         bytes[6] = self.end_sector;
-        bytes[7] = self.end_sector;
+        bytes[7] = self.end_track;
 
         write_to_bytes::<4>(self.lb_start as u64, &mut bytes, 8);
         write_to_bytes::<4>(self.lb_size as u64, &mut bytes, 12);
@@ -174,3 +471,224 @@ impl PartRecord {
         bytes
     }
 }
+
+/// The `(head, sector, track)` bytes addressing `lba`, or `0xFE/0xFF/0xFF` past the last
+/// cylinder.
+fn lba_to_chs(lba: u32, geometry: ChsGeometry) -> (u8, u8, u8) {
+    let sectors = geometry.sectors_per_track;
+    let cylinder = lba / (geometry.heads * sectors);
+    if cylinder > 1023 {
+        return (0xFE, 0xFF, 0xFF);
+    }
+    let head = (lba / sectors) % geometry.heads;
+    let sector = lba % sectors + 1;
+    (
+        head as u8,
+        ((cylinder >> 2) & 0xC0) as u8 | sector as u8,
+        cylinder as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{part_types, testing};
+
+    #[test]
+    fn deserialize_keeps_bootcode_and_disk_signature() {
+        let mut bytes = ProtectiveMBR::new(testing::DISK_LBAS).serialize();
+        bytes[..440].fill(0xAB);
+        bytes[440..444].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+        let mbr = ProtectiveMBR::deserialize(&bytes).unwrap();
+        assert_eq!(mbr.bootcode, [0xAB; 440]);
+        assert_eq!(mbr.disk_signature, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(mbr.serialize(), bytes);
+    }
+
+    #[test]
+    fn from_header_covers_the_whole_disk() {
+        let guid = crate::Uuid::from_bytes([0x12; 16]);
+        let two_tib = 1u64 << 32;
+        for (disk_lba_count, size) in [
+            (testing::DISK_LBAS, testing::DISK_LBAS as u32 - 1),
+            (two_tib, 0xFFFF_FFFF),
+            (2 * two_tib, 0xFFFF_FFFF),
+        ] {
+            let header = Header::new_primary(guid, disk_lba_count, 512).unwrap();
+            let mbr = ProtectiveMBR::from_header(&header, disk_lba_count);
+            assert_eq!(
+                (mbr.partitions[0].lb_start, mbr.partitions[0].lb_size),
+                (1, size)
+            );
+        }
+        // the record follows the disk when the header is for a smaller one
+        let header = Header::new_primary(guid, testing::DISK_LBAS, 512).unwrap();
+        let mbr = ProtectiveMBR::from_header(&header, 2 * testing::DISK_LBAS);
+        assert_eq!(mbr.partitions[0].lb_size, 2 * testing::DISK_LBAS as u32 - 1);
+    }
+
+    #[test]
+    fn geometry_rejects_zero_heads_and_sectors() {
+        assert!(ChsGeometry::new(0, 63).is_none());
+        assert!(ChsGeometry::new(255, 0).is_none());
+        assert!(ChsGeometry::new(256, 63).is_none());
+        assert!(ChsGeometry::new(255, 64).is_none());
+        assert_eq!(ChsGeometry::new(255, 63), Some(ChsGeometry::default()));
+    }
+
+    #[test]
+    fn chs_round_trips_through_lba() {
+        let geometry = ChsGeometry::new(16, 63).unwrap();
+        let mut record = PartRecord::zero();
+        record.set_chs_from_lba(2048, 1_000_000, geometry);
+        let start = (record.start_head, record.start_sector, record.start_track);
+        assert_eq!(
+            PartRecord::chs_to_lba(start.0, start.1, start.2, geometry),
+            Some(2048)
+        );
+        let end = (record.end_head, record.end_sector, record.end_track);
+        assert_eq!(
+            PartRecord::chs_to_lba(end.0, end.1, end.2, geometry),
+            Some(2048 + 1_000_000 - 1)
+        );
+        // past the 1024 cylinders of the default geometry
+        record.set_chs_from_lba(0, u32::MAX, ChsGeometry::default());
+        assert_eq!(
+            (record.end_head, record.end_sector, record.end_track),
+            (0xFE, 0xFF, 0xFF)
+        );
+    }
+
+    #[test]
+    fn new_record_fills_lba_and_chs() {
+        let geometry = ChsGeometry::new(1, 1).unwrap();
+        let record = PartRecord::new(0x83, true, 5, 10, geometry);
+        assert_eq!((record.lb_start, record.lb_size), (5, 10));
+        assert_eq!((record.os_type, record.boot_indicator), (0x83, 0x80));
+        // one sector per cylinder, the cylinder is the LBA
+        assert_eq!(
+            (record.start_head, record.start_sector, record.start_track),
+            (0, 1, 5)
+        );
+        assert_eq!(
+            (record.end_head, record.end_sector, record.end_track),
+            (0, 1, 14)
+        );
+        let far = PartRecord::new(0x07, false, 1 << 30, 8, ChsGeometry::default());
+        assert_eq!(far.boot_indicator, 0x00);
+        assert_eq!(
+            (far.start_head, far.start_sector, far.start_track),
+            (0xFE, 0xFF, 0xFF)
+        );
+    }
+
+    #[test]
+    fn build_hybrid_mbr_marks_the_active_record() {
+        let esp = testing::part(part_types::EFI_SYSTEM, 1, 2048, 4095);
+        let root = testing::part(part_types::LINUX_FILESYSTEM, 2, 4096, 6143);
+        let parts = [(&esp, 0xEF), (&root, 0x83)];
+        let mbr = ProtectiveMBR::build_hybrid_mbr(testing::DISK_LBAS, &parts, Some(1)).unwrap();
+        assert_eq!(mbr.partitions[1].boot_indicator, 0x00);
+        assert_eq!(mbr.partitions[2].boot_indicator, 0x80);
+        assert_eq!(mbr.partitions[0].boot_indicator, 0x00);
+        assert_eq!(
+            (mbr.partitions[0].os_type, mbr.partitions[0].lb_size),
+            (0xEE, 2047)
+        );
+        let none = ProtectiveMBR::build_hybrid_mbr(testing::DISK_LBAS, &parts, None).unwrap();
+        assert!(none
+            .partitions
+            .iter()
+            .all(|record| record.boot_indicator == 0x00));
+        assert!(matches!(
+            ProtectiveMBR::build_hybrid_mbr(testing::DISK_LBAS, &parts, Some(2)),
+            Err(MBRError::InvalidActiveRecord)
+        ));
+    }
+
+    #[test]
+    fn record_is_bounds_checked() {
+        let mut mbr = ProtectiveMBR::new(testing::DISK_LBAS);
+        assert_eq!(mbr.record(0).unwrap().os_type, 0xEE);
+        assert!((1..4).all(|i| mbr.record(i).unwrap().is_zero()));
+        assert!(mbr.record(4).is_none());
+        assert!(mbr.record_mut(4).is_none());
+        mbr.record_mut(3).unwrap().os_type = 0x83;
+        assert_eq!(mbr.partitions[3].os_type, 0x83);
+    }
+
+    #[test]
+    fn new_protective_mbr_serializes_the_0xee_record() {
+        let bytes = ProtectiveMBR::new(testing::DISK_LBAS).serialize();
+        assert_eq!(bytes[446 + 4], 0xEE);
+        assert_eq!(&bytes[446 + 8..446 + 16], &[1, 0, 0, 0, 0xFF, 0x1F, 0, 0]);
+        assert!(bytes[462..510].iter().all(|b| *b == 0));
+        assert_eq!(&bytes[510..], &MBR_SIGNATURE);
+        let huge = ProtectiveMBR::new(1 << 40);
+        assert_eq!(huge.partitions[0].lb_size, 0xFFFF_FFFF);
+    }
+
+    #[test]
+    fn deserialize_rejects_a_short_buffer() {
+        assert!(matches!(
+            ProtectiveMBR::deserialize(&[0u8; 100]),
+            Err(MBRError::InvalidMBRLength)
+        ));
+    }
+
+    #[test]
+    fn record_round_trips_the_end_chs_bytes() {
+        let record = PartRecord {
+            end_head: 0xFE,
+            end_sector: 0xC1,
+            end_track: 0x2A,
+            ..PartRecord::new_protective(Some(8191))
+        };
+        let bytes = record.to_bytes();
+        assert_eq!(bytes[5..8], [0xFE, 0xC1, 0x2A]);
+        assert_eq!(PartRecord::from_bytes(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn validate_wants_a_single_record_over_the_disk() {
+        let disks = testing::DISK_LBAS;
+        assert!(ProtectiveMBR::new(disks).validate(disks).is_ok());
+        assert!(ProtectiveMBR::new(1 << 40).validate(1 << 40).is_ok());
+        let invalid = |mbr: ProtectiveMBR| {
+            matches!(mbr.validate(disks), Err(MBRError::InvalidProtectiveLayout))
+        };
+        assert!(invalid(ProtectiveMBR::new(disks / 2)));
+        assert!(invalid(ProtectiveMBR::default()));
+        let mut two = ProtectiveMBR::new(disks);
+        two.partitions[1] = two.partitions[0];
+        assert!(invalid(two));
+        let mut moved = ProtectiveMBR::new(disks);
+        moved.partitions[0].lb_start = 2;
+        assert!(invalid(moved));
+        let part = testing::part(part_types::EFI_SYSTEM, 1, 2048, 4095);
+        let hybrid = ProtectiveMBR::build_hybrid_mbr(disks, &[(&part, 0xEF)], None).unwrap();
+        assert!(invalid(hybrid));
+    }
+
+    #[test]
+    fn probe_tells_the_schemes_apart() {
+        let mut disk = testing::disk();
+        assert_eq!(probe(&disk, 512), DiskKind::Gpt);
+
+        let part = testing::part(part_types::EFI_SYSTEM, 1, 2048, 4095);
+        let hybrid = ProtectiveMBR::build_hybrid_mbr(testing::DISK_LBAS, &[(&part, 0xEF)], None);
+        disk[..512].copy_from_slice(&hybrid.unwrap().serialize());
+        assert_eq!(probe(&disk, 512), DiskKind::Hybrid);
+
+        let mut mbr = ProtectiveMBR::new(testing::DISK_LBAS);
+        mbr.partitions[0] = part.to_mbr_record(0x83, false).unwrap();
+        assert_eq!(probe(&mbr.serialize(), 512), DiskKind::Mbr);
+
+        // a protective MBR without a GPT header behind it
+        let mut disk = testing::disk();
+        disk[512..1024].fill(0);
+        assert_eq!(probe(&disk, 512), DiskKind::Unknown);
+        assert_eq!(probe(&[0u8; 1024], 512), DiskKind::Unknown);
+        assert_eq!(probe(&testing::disk()[..100], 512), DiskKind::Unknown);
+    }
+}
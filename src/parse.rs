@@ -0,0 +1,510 @@
+use alloc::{vec, vec::Vec};
+
+use super::{
+    io::{self, check_block_size, BlockIo, BlockRead, MemDisk, SliceDisk},
+    GptError, GptLayout, GptWarning, HeaderKind, LayoutError, ProtectiveMBR, PARTITION_LBA_SIZE,
+    PRIMARY_HEADER_LBA, PROTECTIVE_MBR_LBA,
+};
+
+/// The largest image in bytes [`GptLayout::read_from_stream`] buffers, 1 GiB.
+pub const MAX_STREAM_IMAGE_SIZE: usize = 1 << 30;
+
+/// How strict [`GptLayout::load_with_options`] is. The default is strict, what a tool
+/// about to mount or modify the disk wants, [`ParseOptions::lenient`] is for reading damaged
+/// disks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Fail when a header or partition array doesn't match its CRC32
+    pub validate_crc: bool,
+    /// Fail when the MBR is missing or doesn't protect the disk
+    pub require_protective_mbr: bool,
+    /// Rebuild an unreadable header from the other one instead of failing
+    pub allow_backup_fallback: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            validate_crc: true,
+            require_protective_mbr: true,
+            allow_backup_fallback: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Report every problem as a warning and recover whatever can be recovered.
+    pub fn lenient() -> Self {
+        Self {
+            validate_crc: false,
+            require_protective_mbr: false,
+            allow_backup_fallback: true,
+        }
+    }
+}
+
+impl GptLayout {
+    /// Parse a whole disk image held in memory, `block_size` being the LBA size in bytes,
+    /// with the strict [`ParseOptions::default`].
+    pub fn parse(disk: &[u8], block_size: usize) -> Result<Self, GptError> {
+        Self::parse_with_options(disk, block_size, &ParseOptions::default())
+    }
+
+    /// Load the layout from a block device with the strict [`ParseOptions::default`]: both
+    /// headers and arrays must decode and match their CRC32s and the MBR must be
+    /// protective. Any [`BlockRead`] will do, the device doesn't have to be writable.
+    pub fn load<D: BlockRead>(dev: &mut D) -> Result<Self, GptError> {
+        Self::load_with_options(dev, &ParseOptions::default())
+    }
+
+    /// Write the protective MBR, both headers and both partition arrays to the LBAs the
+    /// headers point at. The CRCs are written as they are, see
+    /// [`GptLayout::recompute_crc32`].
+    pub fn store<D: BlockIo>(&self, dev: &mut D) -> Result<(), GptError> {
+        let block_size = dev.block_size();
+        check_block_size(block_size)?;
+        let writes = self.disk_writes(block_size);
+        // check everything fits before touching the disk, a half written layout is worse
+        // than none
+        let fits = writes.iter().all(|(lba, bytes)| {
+            lba.checked_add((bytes.len() / block_size) as u64)
+                .is_some_and(|end| end <= dev.block_count())
+        });
+        if !fits {
+            return Err(LayoutError::DiskTooSmall.into());
+        }
+        for (lba, bytes) in writes.iter().filter(|(_, bytes)| !bytes.is_empty()) {
+            dev.write_block(*lba, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Write the layout to an in-memory disk of `block_size` byte blocks and parse it back,
+    /// failing with [`LayoutError::RoundTripMismatch`] unless the result equals `self`.
+    /// Catches serialization bugs before an image is committed to a real disk.
+    pub fn self_check(&self, block_size: usize) -> Result<(), GptError> {
+        check_block_size(block_size)?;
+        let block_count = self
+            .disk_writes(block_size)
+            .iter()
+            .map(|(lba, bytes)| lba + (bytes.len() / block_size) as u64)
+            .max()
+            .unwrap_or(0);
+        let mut disk = MemDisk::new(block_count, block_size)?;
+        self.store(&mut disk)?;
+        if Self::load(&mut disk)? != *self {
+            return Err(LayoutError::RoundTripMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Parse a disk image from a source which can't seek, like a pipe, by buffering the
+    /// `disk_sectors` sectors of `sector_size` bytes first. Fails with
+    /// [`LayoutError::ImageTooLarge`] for images past [`MAX_STREAM_IMAGE_SIZE`].
+    pub fn read_from_stream<R: io::Read>(
+        reader: &mut R,
+        sector_size: usize,
+        disk_sectors: u64,
+    ) -> Result<Self, GptError> {
+        check_block_size(sector_size)?;
+        let len = usize::try_from(disk_sectors)
+            .ok()
+            .and_then(|sectors| sectors.checked_mul(sector_size))
+            .filter(|len| *len <= MAX_STREAM_IMAGE_SIZE)
+            .ok_or(LayoutError::ImageTooLarge)?;
+        let mut disk = vec![0u8; len];
+        reader.read_exact(&mut disk)?;
+        Self::parse(&disk, sector_size)
+    }
+
+    /// Parse leniently, collecting everything that is wrong instead of failing on the first
+    /// problem, see [`ParseOptions::lenient`]. Only fails if neither header can be read.
+    pub fn read_from_with_report<R: BlockRead>(
+        reader: &mut R,
+    ) -> Result<(Self, Vec<GptWarning>), GptError> {
+        let layout = Self::load_with_options(reader, &ParseOptions::lenient())?;
+        let warnings = layout.warnings.clone();
+        Ok((layout, warnings))
+    }
+
+    /// Parse a whole disk image held in memory as `options` say, see
+    /// [`GptLayout::load_with_options`].
+    pub fn parse_with_options(
+        disk: &[u8],
+        block_size: usize,
+        options: &ParseOptions,
+    ) -> Result<Self, GptError> {
+        Self::load_with_options(&mut SliceDisk::new(disk, block_size)?, options)
+    }
+
+    /// Load the layout from a block device, `options` deciding which problems are errors.
+    /// The ones that aren't, like headers that disagree, entries that aren't 128 bytes, ghost
+    /// entries, partitions outside the usable range or off the 1 MiB alignment, end up in
+    /// [`GptLayout::warnings`]. An unreadable header is
+    /// rebuilt from the other one if `allow_backup_fallback` is set, there is no recovering
+    /// from both being unreadable.
+    pub fn load_with_options<R: BlockRead>(
+        reader: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self, GptError> {
+        let block_size = reader.block_size();
+        check_block_size(block_size)?;
+        let last_lba = reader.block_count().saturating_sub(1);
+        let mut layout = Self::new();
+
+        let mbr = read_blocks(reader, PROTECTIVE_MBR_LBA as u64, 1)?;
+        let strict_mbr = options.require_protective_mbr;
+        match layout.init_protective_mbr(&mbr) {
+            Ok(()) => {
+                if let Err(err) = layout.protective_mbr().validate(reader.block_count()) {
+                    layout.check(strict_mbr, GptWarning::InvalidProtectiveMbr(err), err)?;
+                }
+            }
+            Err(err) => {
+                layout.check(strict_mbr, GptWarning::InvalidProtectiveMbr(err), err)?;
+                layout.set_protective_mbr(ProtectiveMBR::default());
+            }
+        }
+
+        let primary = read_blocks(reader, PRIMARY_HEADER_LBA as u64, 1)?;
+        let primary = layout.init_primary_header(&primary);
+        let backup_lba = match primary {
+            Ok(()) => layout.primary_header().backup_lba.min(last_lba),
+            Err(_) => last_lba,
+        };
+        let backup = read_blocks(reader, backup_lba, 1)?;
+        let backup = layout.init_backup_header(&backup);
+
+        let strict_headers = !options.allow_backup_fallback;
+        match (primary, backup) {
+            (Err(err), Err(_)) => return Err(err.into()),
+            (Err(error), Ok(())) => {
+                let header = HeaderKind::Primary;
+                layout.check(
+                    strict_headers,
+                    GptWarning::InvalidHeader { header, error },
+                    error,
+                )?;
+                layout.read_backup_partitions(reader)?;
+            }
+            (Ok(()), Err(error)) => {
+                let header = HeaderKind::Backup;
+                layout.check(
+                    strict_headers,
+                    GptWarning::InvalidHeader { header, error },
+                    error,
+                )?;
+                layout.read_partitions(reader)?;
+            }
+            (Ok(()), Ok(())) => {
+                layout.read_partitions(reader)?;
+                layout.read_backup_partitions(reader)?;
+            }
+        }
+
+        if primary.is_ok() {
+            let (crc_valid, parts_valid) = (
+                layout.primary_header().crc32_valid(),
+                layout.primary_header().crc32_parts
+                    == crate::crc::crc32(&layout.serialize_partitions()),
+            );
+            layout.check_crc(options, HeaderKind::Primary, crc_valid, parts_valid)?;
+        }
+        if backup.is_ok() {
+            let (crc_valid, parts_valid) = (
+                layout.backup_header().crc32_valid(),
+                layout.backup_header().crc32_parts
+                    == crate::crc::crc32(&layout.serialize_backup_partitions()),
+            );
+            layout.check_crc(options, HeaderKind::Backup, crc_valid, parts_valid)?;
+        }
+        match (primary.is_ok(), backup.is_ok()) {
+            (false, _) => layout.copy_backup_to_primary(),
+            (_, false) => layout.copy_primary_to_backup(reader.block_count(), block_size),
+            _ => {
+                if !layout.primary_matches_backup() {
+                    layout.warnings.push(GptWarning::HeadersMismatch);
+                }
+            }
+        }
+
+        for (header, part_size) in [
+            (HeaderKind::Primary, layout.primary_header().part_size),
+            (HeaderKind::Backup, layout.backup_header().part_size),
+        ] {
+            if part_size != PARTITION_LBA_SIZE as u32 {
+                layout
+                    .warnings
+                    .push(GptWarning::NonStandardEntrySize { header, part_size });
+            }
+        }
+        for index in layout.ghost_entries() {
+            layout.warnings.push(GptWarning::GhostEntry(index));
+        }
+        for index in layout.validate_partition_bounds() {
+            layout
+                .warnings
+                .push(GptWarning::PartitionOutOfBounds(index));
+        }
+        let alignment = (1024 * 1024 / block_size as u64).max(1);
+        for (part, index) in layout.partitions.iter() {
+            if part.alignment_offset(alignment) != 0 {
+                layout
+                    .warnings
+                    .push(GptWarning::MisalignedPartition(*index));
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Fail with `error` if `strict`, otherwise record `warning` and carry on.
+    fn check(
+        &mut self,
+        strict: bool,
+        warning: GptWarning,
+        error: impl Into<GptError>,
+    ) -> Result<(), GptError> {
+        if strict {
+            return Err(error.into());
+        }
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    /// Report the CRC checks of the `header` header as errors or warnings as `options`
+    /// say.
+    fn check_crc(
+        &mut self,
+        options: &ParseOptions,
+        header: HeaderKind,
+        crc_valid: bool,
+        parts_valid: bool,
+    ) -> Result<(), GptError> {
+        if !crc_valid {
+            self.check(
+                options.validate_crc,
+                GptWarning::HeaderCrcMismatch(header),
+                LayoutError::HeaderCrcMismatch(header),
+            )?;
+        }
+        if !parts_valid {
+            self.check(
+                options.validate_crc,
+                GptWarning::PartitionTableCrcMismatch(header),
+                LayoutError::PartitionTableCrcMismatch(header),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Read and decode the primary partition array the primary header points at.
+    fn read_partitions<R: BlockRead>(&mut self, reader: &mut R) -> Result<(), GptError> {
+        let block_size = reader.block_size();
+        let header = self.primary_header();
+        let (num_parts, part_size) = (header.num_parts, header.part_size);
+        let array = read_blocks(
+            reader,
+            header.part_start,
+            header.table_sectors(block_size as u64),
+        )?;
+        self.init_all_partitions(&array, num_parts, part_size)?;
+        Ok(())
+    }
+
+    /// Read and decode the backup partition array the backup header points at.
+    fn read_backup_partitions<R: BlockRead>(&mut self, reader: &mut R) -> Result<(), GptError> {
+        let block_size = reader.block_size();
+        let header = self.backup_header();
+        let (num_parts, part_size) = (header.num_parts, header.part_size);
+        let array = read_blocks(
+            reader,
+            header.part_start,
+            header.table_sectors(block_size as u64),
+        )?;
+        self.init_all_backup_partitions(&array, num_parts, part_size)?;
+        Ok(())
+    }
+}
+
+/// Read `count` blocks starting at `lba`, failing with [`LayoutError::DiskTooSmall`] if they
+/// lie past the end of the disk.
+fn read_blocks<R: BlockRead>(reader: &mut R, lba: u64, count: u64) -> Result<Vec<u8>, GptError> {
+    if lba
+        .checked_add(count)
+        .is_none_or(|end| end > reader.block_count())
+    {
+        return Err(LayoutError::DiskTooSmall.into());
+    }
+    let mut blocks = vec![0u8; count as usize * reader.block_size()];
+    // an empty partition array (num_parts = 0) has nothing to read
+    if count > 0 {
+        reader.read_block(lba, &mut blocks)?;
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::{
+        io::MemDisk, testing, BlockError, GptError, GptLayout, GptWarning, Header, HeaderKind,
+        LayoutError, ParseOptions, HEADER_SIZE, MAX_STREAM_IMAGE_SIZE,
+    };
+
+    #[test]
+    fn lenient_rebuilds_backup_at_disk_end() {
+        let mut disk = testing::disk();
+        let mut header = Header::deserialize(&disk[512..1024]).unwrap();
+        header.backup_lba = u64::MAX;
+        header.crc32 = header.calculate_crc32();
+        disk[512..512 + HEADER_SIZE].copy_from_slice(&header.serialize());
+        let last = (testing::DISK_LBAS as usize - 1) * 512;
+        disk[last..].fill(0);
+        let layout = GptLayout::parse_with_options(&disk, 512, &ParseOptions::lenient()).unwrap();
+        assert_eq!(layout.backup_header().my_lba, testing::DISK_LBAS - 1);
+        assert_eq!(layout.backup_header().part_start, testing::DISK_LBAS - 33);
+        assert_eq!(layout.backup_partitions().len(), 3);
+    }
+
+    #[test]
+    fn parse_rejects_corrupt_header() {
+        let mut disk = testing::disk();
+        assert!(GptLayout::parse(&disk, 512).is_ok());
+        // first_usable of the primary header
+        disk[512 + 40] ^= 1;
+        assert!(matches!(
+            GptLayout::parse(&disk, 512),
+            Err(GptError::Layout(LayoutError::HeaderCrcMismatch(
+                HeaderKind::Primary
+            )))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_corrupt_partition_array() {
+        let mut disk = testing::disk();
+        // start_lba of the first entry of the backup array
+        let backup_array = (testing::DISK_LBAS as usize - 33) * 512;
+        disk[backup_array + 32] ^= 1;
+        assert!(matches!(
+            GptLayout::parse(&disk, 512),
+            Err(GptError::Layout(LayoutError::PartitionTableCrcMismatch(
+                HeaderKind::Backup
+            )))
+        ));
+    }
+
+    #[test]
+    fn read_from_stream_rejects_oversized_images() {
+        let disk = testing::disk();
+        assert_eq!(
+            GptLayout::read_from_stream(&mut disk.as_slice(), 512, testing::DISK_LBAS).unwrap(),
+            testing::layout()
+        );
+        for sectors in [u64::MAX, (MAX_STREAM_IMAGE_SIZE / 512 + 1) as u64] {
+            assert!(matches!(
+                GptLayout::read_from_stream(&mut disk.as_slice(), 512, sectors),
+                Err(GptError::Layout(LayoutError::ImageTooLarge))
+            ));
+        }
+    }
+
+    #[test]
+    fn report_collects_every_problem() {
+        let mut disk = testing::disk();
+        // end_lba of the fifth, unused, primary entry
+        disk[1024 + 4 * 128 + 40] = 1;
+        // disk_guid of the backup header
+        disk[(testing::DISK_LBAS as usize - 1) * 512 + 60] ^= 1;
+        let (layout, warnings) =
+            GptLayout::read_from_with_report(&mut MemDisk::from_vec(disk, 512).unwrap()).unwrap();
+        assert!(matches!(
+            warnings[..],
+            [
+                GptWarning::PartitionTableCrcMismatch(HeaderKind::Primary),
+                GptWarning::HeaderCrcMismatch(HeaderKind::Backup),
+                GptWarning::HeadersMismatch,
+                GptWarning::GhostEntry(5),
+            ]
+        ));
+        assert_eq!(layout.partition_count(), 3);
+        assert_eq!(layout.partition(1).unwrap().start_lba, 2048);
+    }
+
+    #[test]
+    fn empty_partition_array_parses() {
+        let mut primary = Header::with_geometry(testing::DISK_LBAS, 0, 128, 512).unwrap();
+        primary.disk_guid = crate::Uuid::from_bytes([0x12; 16]);
+        let mut layout = GptLayout::new();
+        layout.set_protective_mbr(crate::ProtectiveMBR::new(testing::DISK_LBAS));
+        layout.set_backup_header(primary.as_backup(testing::DISK_LBAS, 512));
+        layout.set_primary_header(primary);
+        layout.recompute_crc32();
+        let mut disk = MemDisk::new(testing::DISK_LBAS, 512).unwrap();
+        layout.store(&mut disk).unwrap();
+
+        let parsed = GptLayout::load(&mut disk).unwrap();
+        assert_eq!(parsed.partition_count(), 0);
+        assert!(parsed.serialize_partitions().is_empty());
+        assert_eq!(parsed.primary_header().crc32_parts, crate::crc::crc32(&[]));
+        assert_eq!(parsed.valid_headers(), (true, true));
+        assert!(parsed.warnings().is_empty());
+    }
+
+    #[test]
+    fn self_check_catches_what_doesnt_serialize() {
+        let mut layout = testing::layout();
+        layout.self_check(512).unwrap();
+        // reserved bytes past a part_size of 128 have nowhere to go
+        layout.partition_mut(1).unwrap().reserved = vec![1];
+        assert!(matches!(
+            layout.self_check(512),
+            Err(GptError::Layout(LayoutError::RoundTripMismatch))
+        ));
+    }
+
+    #[test]
+    fn strict_parse_still_reports_findings() {
+        assert!(GptLayout::parse(&testing::disk(), 512)
+            .unwrap()
+            .warnings()
+            .is_empty());
+        let mut layout = testing::layout();
+        layout.partition_mut(3).unwrap().start_lba = 6200;
+        layout.sync_backup();
+        layout.recompute_crc32();
+        let mut disk = MemDisk::new(testing::DISK_LBAS, 512).unwrap();
+        layout.store(&mut disk).unwrap();
+        let parsed = GptLayout::parse(disk.as_slice(), 512).unwrap();
+        assert!(matches!(
+            parsed.warnings(),
+            [GptWarning::MisalignedPartition(3)]
+        ));
+    }
+
+    #[test]
+    fn zero_block_size_is_rejected() {
+        let disk = testing::disk();
+        assert!(matches!(
+            GptLayout::parse(&disk, 0),
+            Err(GptError::Block(BlockError::InvalidBlockSize))
+        ));
+        assert!(matches!(
+            GptLayout::read_from_stream(&mut &disk[..], 0, testing::DISK_LBAS),
+            Err(GptError::Block(BlockError::InvalidBlockSize))
+        ));
+        assert!(matches!(
+            testing::layout().self_check(0),
+            Err(GptError::Block(BlockError::InvalidBlockSize))
+        ));
+        assert!(matches!(
+            MemDisk::new(testing::DISK_LBAS, 0),
+            Err(BlockError::InvalidBlockSize)
+        ));
+        assert!(matches!(
+            MemDisk::from_vec(disk, 0),
+            Err(BlockError::InvalidBlockSize)
+        ));
+    }
+}
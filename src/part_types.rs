@@ -0,0 +1,120 @@
+//! Well-known partition type GUIDs and their human readable names.
+
+use super::Uuid;
+
+/// EFI system partition, holds the bootloaders.
+pub const EFI_SYSTEM: Uuid = Uuid::from_fields(
+    0xC12A7328,
+    0xF81F,
+    0x11D2,
+    [0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B],
+);
+/// BIOS boot partition, where GRUB puts its core image on GPT disks booted by BIOS.
+pub const BIOS_BOOT: Uuid = Uuid::from_fields(
+    0x21686148,
+    0x6449,
+    0x6E6F,
+    [0x74, 0x4E, 0x65, 0x65, 0x64, 0x45, 0x46, 0x49],
+);
+/// Microsoft reserved partition.
+pub const MICROSOFT_RESERVED: Uuid = Uuid::from_fields(
+    0xE3C9E316,
+    0x0B5C,
+    0x4DB8,
+    [0x81, 0x7D, 0xF9, 0x2D, 0xF0, 0x02, 0x15, 0xAE],
+);
+/// Microsoft basic data partition, NTFS, exFAT and FAT volumes.
+pub const MICROSOFT_BASIC_DATA: Uuid = Uuid::from_fields(
+    0xEBD0A0A2,
+    0xB9E5,
+    0x4433,
+    [0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7],
+);
+/// Windows recovery environment.
+pub const WINDOWS_RECOVERY: Uuid = Uuid::from_fields(
+    0xDE94BBA4,
+    0x06D1,
+    0x4D40,
+    [0xA1, 0x6A, 0xBF, 0xD5, 0x01, 0x79, 0xD6, 0xAC],
+);
+/// Linux filesystem data.
+pub const LINUX_FILESYSTEM: Uuid = Uuid::from_fields(
+    0x0FC63DAF,
+    0x8483,
+    0x4772,
+    [0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4],
+);
+/// Linux swap.
+pub const LINUX_SWAP: Uuid = Uuid::from_fields(
+    0x0657FD6D,
+    0xA4AB,
+    0x43C4,
+    [0x84, 0xE5, 0x09, 0x33, 0xC8, 0x4B, 0x4F, 0x4F],
+);
+/// Linux LVM physical volume.
+pub const LINUX_LVM: Uuid = Uuid::from_fields(
+    0xE6D6D379,
+    0xF507,
+    0x44C2,
+    [0xA2, 0x3C, 0x23, 0x8F, 0x2A, 0x3D, 0xF9, 0x28],
+);
+/// Linux software RAID member.
+pub const LINUX_RAID: Uuid = Uuid::from_fields(
+    0xA19D880F,
+    0x05FC,
+    0x4D3B,
+    [0xA0, 0x06, 0x74, 0x3F, 0x0F, 0x84, 0x91, 0x1E],
+);
+/// ChromeOS kernel, see the priority/tries attributes.
+pub const CHROMEOS_KERNEL: Uuid = Uuid::from_fields(
+    0xFE3A2A5D,
+    0x4F32,
+    0x41A7,
+    [0xB7, 0x25, 0xAC, 0xCC, 0x32, 0x85, 0xA3, 0x09],
+);
+/// ChromeOS root filesystem.
+pub const CHROMEOS_ROOTFS: Uuid = Uuid::from_fields(
+    0x3CB8E202,
+    0x3B7E,
+    0x47DD,
+    [0x8A, 0x3C, 0x7F, 0xF2, 0xA1, 0x3C, 0xFC, 0xEC],
+);
+/// Apple HFS+.
+pub const APPLE_HFS_PLUS: Uuid = Uuid::from_fields(
+    0x48465300,
+    0x0000,
+    0x11AA,
+    [0xAA, 0x11, 0x00, 0x30, 0x65, 0x43, 0xEC, 0xAC],
+);
+/// Apple APFS container.
+pub const APPLE_APFS: Uuid = Uuid::from_fields(
+    0x7C3457EF,
+    0x0000,
+    0x11AA,
+    [0xAA, 0x11, 0x00, 0x30, 0x65, 0x43, 0xEC, 0xAC],
+);
+
+/// Every type this module knows with its name.
+pub const KNOWN_TYPES: &[(Uuid, &str)] = &[
+    (EFI_SYSTEM, "EFI System"),
+    (BIOS_BOOT, "BIOS boot"),
+    (MICROSOFT_RESERVED, "Microsoft reserved"),
+    (MICROSOFT_BASIC_DATA, "Microsoft basic data"),
+    (WINDOWS_RECOVERY, "Windows recovery environment"),
+    (LINUX_FILESYSTEM, "Linux filesystem"),
+    (LINUX_SWAP, "Linux swap"),
+    (LINUX_LVM, "Linux LVM"),
+    (LINUX_RAID, "Linux RAID"),
+    (CHROMEOS_KERNEL, "ChromeOS kernel"),
+    (CHROMEOS_ROOTFS, "ChromeOS root filesystem"),
+    (APPLE_HFS_PLUS, "Apple HFS+"),
+    (APPLE_APFS, "Apple APFS"),
+];
+
+/// The name of the partition type `type_guid`, `None` if it isn't a well-known one.
+pub fn name(type_guid: &Uuid) -> Option<&'static str> {
+    KNOWN_TYPES
+        .iter()
+        .find(|(guid, _)| guid == type_guid)
+        .map(|(_, name)| *name)
+}
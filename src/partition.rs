@@ -1,11 +1,15 @@
-use core::fmt::Display;
+use core::{char::DecodeUtf16Error, fmt::Display};
 
-use super::{copy_bytes, write_to_bytes, Uuid};
-use alloc::string::String;
+use super::{
+    copy_bytes, part_types, write_to_bytes, ChsGeometry, PartRecord, PartitionError,
+    PartitionNameError, Uuid,
+};
+use alloc::{format, string::String, vec, vec::Vec};
 use byteorder::{ByteOrder, LittleEndian};
 pub const PARTITION_LBA_SIZE: usize = 128;
 pub const MIN_PARTITION_NUM: usize = 128;
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Partition {
     /// GUID of the partition type.
     pub part_type_guid: Uuid,
@@ -19,8 +23,41 @@ pub struct Partition {
     pub attrs: u64,
     /// Partition name.
     pub name: PartitionName,
+    /// Bytes past the first 128 of the entry when the header's `part_size` is larger,
+    /// reserved by the spec but used by some vendors, kept so they survive a rewrite.
+    pub reserved: Vec<u8>,
 }
 
+/// `bytes` in the largest binary unit it reaches with one decimal truncated, e.g.
+/// `1.5 GiB`, or `512 B` below 1 KiB.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    let Some(exp) = (1..=UNITS.len() as u32)
+        .rev()
+        .find(|exp| bytes >> (10 * exp) != 0)
+    else {
+        return format!("{bytes} B");
+    };
+    let tenths = (bytes as u128 * 10) >> (10 * exp);
+    format!(
+        "{}.{} {}",
+        tenths / 10,
+        tenths % 10,
+        UNITS[exp as usize - 1]
+    )
+}
+
+/// What an unused slot of the partition array holds.
+pub(crate) static EMPTY_PARTITION: Partition = Partition {
+    part_type_guid: Uuid::NIL,
+    part_guid: Uuid::NIL,
+    start_lba: 0,
+    end_lba: 0,
+    attrs: 0,
+    name: PartitionName([0u8; 72]),
+    reserved: Vec::new(),
+};
+
 impl Default for Partition {
     fn default() -> Self {
         Self {
@@ -30,30 +67,40 @@ impl Default for Partition {
             end_lba: Default::default(),
             attrs: Default::default(),
             name: PartitionName([0u8; 72]),
+            reserved: Vec::new(),
         }
     }
 }
 
 impl Partition {
-    pub fn deserialize(blk: &[u8]) -> Option<Self> {
-        let part_type_guid = Uuid::from(&blk[0..16]);
-        let part_guid = Uuid::from(&blk[16..32]);
-        let start_lba = LittleEndian::read_u64(&blk[32..40]);
-        let end_lba = LittleEndian::read_u64(&blk[40..48]);
-        let attrs = LittleEndian::read_u64(&blk[48..56]);
-        let name = PartitionName::from(&blk[56..PARTITION_LBA_SIZE]);
-        if part_type_guid.validate() {
-            Some(Self {
-                part_type_guid,
-                part_guid,
-                start_lba,
-                end_lba,
-                attrs,
-                name,
-            })
-        } else {
-            None
+    /// Reset the entry to what an unused slot holds, same as [`Partition::default`].
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Parse a partition entry, `blk` holds the whole entry (`part_size` bytes), anything
+    /// past the first 128 bytes ends up in `reserved`. `Ok(None)` is an empty slot (nil type
+    /// GUID), a buffer shorter than an entry is an error.
+    pub fn deserialize(blk: &[u8]) -> Result<Option<Self>, PartitionError> {
+        if blk.len() < PARTITION_LBA_SIZE {
+            return Err(PartitionError::InvalidEntryLength);
         }
+        let part = Self::deserialize_unchecked(blk)?;
+        Ok(part.is_used().then_some(part))
+    }
+
+    /// Parse a partition entry without treating a nil type GUID as an empty slot, `blk`
+    /// must be at least 128 bytes.
+    pub(crate) fn deserialize_unchecked(blk: &[u8]) -> Result<Self, PartitionError> {
+        Ok(Self {
+            part_type_guid: Uuid::from(&blk[0..16]),
+            part_guid: Uuid::from(&blk[16..32]),
+            start_lba: LittleEndian::read_u64(&blk[32..40]),
+            end_lba: LittleEndian::read_u64(&blk[40..48]),
+            attrs: LittleEndian::read_u64(&blk[48..56]),
+            name: PartitionName::try_from_bytes(&blk[56..PARTITION_LBA_SIZE])?,
+            reserved: blk[PARTITION_LBA_SIZE..].to_vec(),
+        })
     }
 
     pub fn serialize(&self) -> [u8; PARTITION_LBA_SIZE] {
@@ -66,20 +113,385 @@ impl Partition {
         copy_bytes(&self.name.0, &mut bytes, 56, 72);
         bytes
     }
+
+    /// Whether the entry holds a partition, unused entries have a nil type GUID.
+    pub fn is_used(&self) -> bool {
+        self.part_type_guid.validate()
+    }
+
+    /// Number of LBAs the partition spans, `end_lba` being inclusive. 0 if `end_lba` is
+    /// before `start_lba`.
+    pub fn size_lba(&self) -> u64 {
+        self.end_lba
+            .saturating_add(1)
+            .saturating_sub(self.start_lba)
+    }
+
+    /// Size of the partition in bytes for blocks of `block_size` bytes, saturating at
+    /// `u64::MAX` like [`Partition::size_lba`].
+    pub fn byte_size(&self, block_size: u64) -> u64 {
+        self.size_lba().saturating_mul(block_size)
+    }
+
+    /// The byte offsets the partition covers on a disk of `block_size` byte blocks, the
+    /// slice of a whole disk image holding its contents. Both ends saturate at `u64::MAX`.
+    pub fn byte_range(&self, block_size: u64) -> core::ops::Range<u64> {
+        let start = self.start_lba.saturating_mul(block_size);
+        start..start.saturating_add(self.byte_size(block_size))
+    }
+
+    /// [`format_size`] of the partition for blocks of `block_size` bytes.
+    pub fn size_string(&self, block_size: u64) -> String {
+        format_size(self.byte_size(block_size))
+    }
+
+    /// How far `start_lba` is past the previous multiple of `alignment` (in LBAs). An
+    /// `alignment` of 0 counts as 1 like in [`GptLayout::next_aligned_start`].
+    ///
+    /// [`GptLayout::next_aligned_start`]: crate::GptLayout::next_aligned_start
+    pub fn alignment_offset(&self, alignment: u64) -> u64 {
+        self.start_lba % alignment.max(1)
+    }
+
+    /// Whether this is an EFI system partition.
+    pub fn is_efi_system(&self) -> bool {
+        self.part_type_guid == part_types::EFI_SYSTEM
+    }
+
+    /// Whether this is a Microsoft basic data partition.
+    pub fn is_microsoft_basic_data(&self) -> bool {
+        self.part_type_guid == part_types::MICROSOFT_BASIC_DATA
+    }
+
+    /// Whether this is a Linux filesystem data partition.
+    pub fn is_linux_filesystem(&self) -> bool {
+        self.part_type_guid == part_types::LINUX_FILESYSTEM
+    }
+
+    /// Whether this is a Linux swap partition.
+    pub fn is_linux_swap(&self) -> bool {
+        self.part_type_guid == part_types::LINUX_SWAP
+    }
+
+    /// Whether this is a BIOS boot partition.
+    pub fn is_bios_boot(&self) -> bool {
+        self.part_type_guid == part_types::BIOS_BOOT
+    }
+
+    /// ChromeOS kernel boot priority, attribute bits 48 to 51, 0 means not bootable.
+    pub fn cros_priority(&self) -> u8 {
+        self.attr_bits(48, 4) as u8
+    }
+
+    /// Set the ChromeOS boot priority, only the low 4 bits of `priority` are kept.
+    pub fn set_cros_priority(&mut self, priority: u8) {
+        self.set_attr_bits(48, 4, priority as u64);
+    }
+
+    /// Boot attempts ChromeOS has left for the kernel, attribute bits 52 to 55.
+    pub fn cros_tries(&self) -> u8 {
+        self.attr_bits(52, 4) as u8
+    }
+
+    /// Set the ChromeOS tries left, only the low 4 bits of `tries` are kept.
+    pub fn set_cros_tries(&mut self, tries: u8) {
+        self.set_attr_bits(52, 4, tries as u64);
+    }
+
+    /// Whether the ChromeOS kernel booted successfully, attribute bit 56.
+    pub fn cros_successful(&self) -> bool {
+        self.attr_bits(56, 1) != 0
+    }
+
+    pub fn set_cros_successful(&mut self, successful: bool) {
+        self.set_attr_bits(56, 1, successful as u64);
+    }
+
+    /// Whether Windows mounts the volume read-only, attribute bit 60.
+    pub fn is_read_only(&self) -> bool {
+        self.attr_bits(60, 1) != 0
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.set_attr_bits(60, 1, read_only as u64);
+    }
+
+    /// Whether the volume is a shadow copy, attribute bit 61.
+    pub fn is_shadow_copy(&self) -> bool {
+        self.attr_bits(61, 1) != 0
+    }
+
+    pub fn set_shadow_copy(&mut self, shadow_copy: bool) {
+        self.set_attr_bits(61, 1, shadow_copy as u64);
+    }
+
+    /// Whether Windows hides the volume, attribute bit 62.
+    pub fn is_hidden(&self) -> bool {
+        self.attr_bits(62, 1) != 0
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.set_attr_bits(62, 1, hidden as u64);
+    }
+
+    /// Whether Windows leaves the volume without a default drive letter, attribute bit 63.
+    pub fn is_no_automount(&self) -> bool {
+        self.attr_bits(63, 1) != 0
+    }
+
+    pub fn set_no_automount(&mut self, no_automount: bool) {
+        self.set_attr_bits(63, 1, no_automount as u64);
+    }
+
+    /// The attribute flags as a [`PartitionAttributes`], a copy of `attrs`.
+    pub fn attributes(&self) -> PartitionAttributes {
+        PartitionAttributes(self.attrs)
+    }
+
+    pub fn set_attributes(&mut self, attributes: PartitionAttributes) {
+        self.attrs = attributes.into();
+    }
+
+    /// The `width` attribute bits starting at bit `shift`.
+    fn attr_bits(&self, shift: u32, width: u32) -> u64 {
+        (self.attrs >> shift) & ((1 << width) - 1)
+    }
+
+    /// Overwrite the `width` attribute bits starting at bit `shift` with the low bits of
+    /// `value`, leaving the others alone.
+    fn set_attr_bits(&mut self, shift: u32, width: u32, value: u64) {
+        let mask = ((1 << width) - 1) << shift;
+        self.attrs = (self.attrs & !mask) | ((value << shift) & mask);
+    }
+
+    /// The MBR record covering the same LBAs, with CHS addresses for the default
+    /// [`ChsGeometry`] and marked active if `bootable`. `None` if the partition doesn't fit
+    /// the 32-bit LBA range of MBR, i.e. reaches past 2 TiB on 512 byte blocks.
+    pub fn to_mbr_record(&self, os_type: u8, bootable: bool) -> Option<PartRecord> {
+        let lb_start = u32::try_from(self.start_lba).ok()?;
+        let lb_end = u32::try_from(self.end_lba).ok()?;
+        let lb_size = lb_end.checked_sub(lb_start)?.checked_add(1)?;
+        Some(PartRecord::new(
+            os_type,
+            bootable,
+            lb_start,
+            lb_size,
+            ChsGeometry::default(),
+        ))
+    }
+
+    /// Whether `start_lba` is a multiple of `alignment_lba`, e.g. 2048 for 1 MiB on 512
+    /// byte blocks. Every partition is aligned to 0, which counts as 1 like in
+    /// [`Partition::alignment_offset`].
+    pub fn is_aligned(&self, alignment_lba: u64) -> bool {
+        self.alignment_offset(alignment_lba) == 0
+    }
+
+    /// Serialize the entry into `part_size` bytes, the first 128 are the standard
+    /// fields followed by the `reserved` bytes, anything past those is zero. A `part_size`
+    /// below 128 is raised to 128 so the standard fields always fit.
+    pub fn serialize_entry(&self, part_size: usize) -> Vec<u8> {
+        let part_size = part_size.max(PARTITION_LBA_SIZE);
+        let mut bytes = vec![0u8; part_size];
+        bytes[..PARTITION_LBA_SIZE].copy_from_slice(&self.serialize());
+        let len = self.reserved.len().min(part_size - PARTITION_LBA_SIZE);
+        bytes[PARTITION_LBA_SIZE..PARTITION_LBA_SIZE + len].copy_from_slice(&self.reserved[..len]);
+        bytes
+    }
+}
+
+impl Partition {
+    /// The type GUID with its name in front when it is a well-known type, e.g.
+    /// `EFI System (c12a7328-f81f-11d2-ba4b-00a0c93ec93b)`.
+    fn type_label(&self) -> String {
+        match part_types::name(&self.part_type_guid) {
+            Some(name) => format!("{name} ({})", self.part_type_guid),
+            None => format!("{}", self.part_type_guid),
+        }
+    }
+
+    /// A one line summary: type, name and size, e.g.
+    /// `EFI System (c12a7328-f81f-11d2-ba4b-00a0c93ec93b) "EFI" 2048 sectors`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} \"{}\" {} sectors",
+            self.type_label(),
+            self.name,
+            self.size_lba()
+        )
+    }
 }
 
 impl Display for Partition {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(
-            f,
-            "Partition Entry{{\n
-            \tpartition type guid:{},\n
-            \tpartition guid: {},\n
-            \tstart_lba: {},\n
-            \tend_lba: {},\n
-            \tname: {},\n}}",
-            self.part_type_guid, self.part_guid, self.start_lba, self.end_lba, self.name
-        )
+        writeln!(f, "Partition Entry {{")?;
+        writeln!(f, "    partition type guid: {}", self.type_label())?;
+        writeln!(f, "    partition guid: {}", self.part_guid)?;
+        writeln!(f, "    start_lba: {}", self.start_lba)?;
+        writeln!(f, "    end_lba: {}", self.end_lba)?;
+        writeln!(f, "    size: {} sectors", self.size_lba())?;
+        writeln!(f, "    name: {}", self.name)?;
+        write!(f, "}}")
+    }
+}
+
+/// Builds a [`Partition`], checking it can actually be written to an entry.
+#[derive(Clone, Debug, Default)]
+pub struct PartitionBuilder {
+    type_guid: Uuid,
+    guid: Uuid,
+    start_lba: u64,
+    end_lba: u64,
+    name: String,
+    attrs: u64,
+}
+
+impl PartitionBuilder {
+    /// A builder for an unnamed partition with no attributes, the type GUID and the LBA
+    /// range have to be set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn type_guid(mut self, type_guid: Uuid) -> Self {
+        self.type_guid = type_guid;
+        self
+    }
+
+    /// The unique GUID of the partition, nil unless set, see
+    /// [`GptLayout::assign_missing_guids`](crate::GptLayout::assign_missing_guids).
+    pub fn guid(mut self, guid: Uuid) -> Self {
+        self.guid = guid;
+        self
+    }
+
+    pub fn start_lba(mut self, start_lba: u64) -> Self {
+        self.start_lba = start_lba;
+        self
+    }
+
+    /// Last LBA of the partition, inclusive.
+    pub fn end_lba(mut self, end_lba: u64) -> Self {
+        self.end_lba = end_lba;
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    pub fn attrs(mut self, attrs: u64) -> Self {
+        self.attrs = attrs;
+        self
+    }
+
+    pub fn build(&self) -> Result<Partition, PartitionError> {
+        if self.type_guid.is_nil() {
+            return Err(PartitionError::MissingTypeGuid);
+        }
+        if self.end_lba < self.start_lba {
+            return Err(PartitionError::InvalidRange);
+        }
+        Ok(Partition {
+            part_type_guid: self.type_guid,
+            part_guid: self.guid,
+            start_lba: self.start_lba,
+            end_lba: self.end_lba,
+            attrs: self.attrs,
+            name: PartitionName::new(&self.name)?,
+            reserved: Vec::new(),
+        })
+    }
+}
+
+/// The 64 attribute bits of a partition entry: the three flags the spec defines at the
+/// bottom, the 16 bits from 48 up whose meaning depends on the partition type and the
+/// reserved ones in between.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PartitionAttributes(pub u64);
+
+impl PartitionAttributes {
+    /// Bit 0, the platform needs the partition to function and it must not be deleted.
+    pub const REQUIRED: u64 = 1 << 0;
+    /// Bit 1, EFI firmware must not produce a block IO protocol for the partition.
+    pub const NO_BLOCK_IO: u64 = 1 << 1;
+    /// Bit 2, legacy BIOS may boot from the partition.
+    pub const LEGACY_BIOS_BOOTABLE: u64 = 1 << 2;
+    /// Bits 48 to 63, defined per partition type.
+    pub const TYPE_SPECIFIC_MASK: u64 = 0xFFFF << 48;
+
+    /// Whether all bits of `flags` are set.
+    pub fn contains(&self, flags: u64) -> bool {
+        self.0 & flags == flags
+    }
+
+    /// Set or clear all bits of `flags`.
+    pub fn set(&mut self, flags: u64, value: bool) {
+        if value {
+            self.0 |= flags;
+        } else {
+            self.0 &= !flags;
+        }
+    }
+
+    pub fn required(&self) -> bool {
+        self.contains(Self::REQUIRED)
+    }
+
+    pub fn set_required(&mut self, required: bool) {
+        self.set(Self::REQUIRED, required);
+    }
+
+    pub fn no_block_io(&self) -> bool {
+        self.contains(Self::NO_BLOCK_IO)
+    }
+
+    pub fn set_no_block_io(&mut self, no_block_io: bool) {
+        self.set(Self::NO_BLOCK_IO, no_block_io);
+    }
+
+    pub fn legacy_bios_bootable(&self) -> bool {
+        self.contains(Self::LEGACY_BIOS_BOOTABLE)
+    }
+
+    pub fn set_legacy_bios_bootable(&mut self, bootable: bool) {
+        self.set(Self::LEGACY_BIOS_BOOTABLE, bootable);
+    }
+
+    /// The type specific bits 48 to 63, shifted down.
+    pub fn type_specific(&self) -> u16 {
+        (self.0 >> 48) as u16
+    }
+
+    pub fn set_type_specific(&mut self, bits: u16) {
+        self.0 = (self.0 & !Self::TYPE_SPECIFIC_MASK) | (bits as u64) << 48;
+    }
+}
+
+impl From<u64> for PartitionAttributes {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<PartitionAttributes> for u64 {
+    fn from(value: PartitionAttributes) -> Self {
+        value.0
+    }
+}
+
+impl core::fmt::Debug for PartitionAttributes {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PartitionAttributes")
+            .field("required", &self.required())
+            .field("no_block_io", &self.no_block_io())
+            .field("legacy_bios_bootable", &self.legacy_bios_bootable())
+            .field(
+                "type_specific",
+                &format_args!("{:#06x}", self.type_specific()),
+            )
+            .finish()
     }
 }
 
@@ -94,11 +506,347 @@ impl From<&[u8]> for PartitionName {
     }
 }
 
+impl PartitionName {
+    /// Take the raw 72 byte UTF-16LE name field of an entry, erroring on any other length
+    /// instead of panicking like the `From` impl does.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, PartitionError> {
+        let name = bytes
+            .try_into()
+            .map_err(|_| PartitionError::InvalidEntryLength)?;
+        Ok(Self(name))
+    }
+
+    /// Encode `name` as UTF-16LE, null padded. Errors if it takes more than the 36 code
+    /// units an entry has room for, characters outside the BMP taking two as a surrogate
+    /// pair.
+    pub fn new(name: &str) -> Result<Self, PartitionError> {
+        let mut bytes = [0u8; 72];
+        let mut units = bytes.chunks_exact_mut(2);
+        for unit in name.encode_utf16() {
+            let dst = units.next().ok_or(PartitionError::NameTooLong)?;
+            dst.copy_from_slice(&unit.to_le_bytes());
+        }
+        Ok(Self(bytes))
+    }
+
+    /// The UTF-16LE code units of the name up to the first null.
+    fn code_units(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|unit| *unit != 0)
+    }
+
+    /// Number of UTF-16 code units before the first null, out of the 36 an entry has room
+    /// for. A character outside the BMP counts as two.
+    pub fn char_len(&self) -> usize {
+        self.code_units().count()
+    }
+
+    /// Whether the name is blank.
+    pub fn is_empty(&self) -> bool {
+        self.code_units().next().is_none()
+    }
+
+    /// Whether both names hold the same text, unlike `==` whatever follows the first null
+    /// code unit is ignored.
+    pub fn equals_text(&self, other: &PartitionName) -> bool {
+        self.code_units().eq(other.code_units())
+    }
+
+    /// The characters of the name up to the first null, surrogate pairs combined.
+    fn decode(&self) -> impl Iterator<Item = Result<char, DecodeUtf16Error>> + '_ {
+        char::decode_utf16(self.code_units())
+    }
+
+    /// Decode the name from UTF-16LE, trailing nulls trimmed, surrogate pairs combined and
+    /// invalid code units replaced by U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        self.decode()
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+
+    /// Decode the name like [`PartitionName::to_string_lossy`] but error on the first
+    /// unpaired surrogate, telling a corrupt name field from one that is merely odd.
+    pub fn try_to_string(&self) -> Result<String, PartitionNameError> {
+        self.decode()
+            .map(|c| {
+                c.map_err(|err| PartitionNameError::UnpairedSurrogate(err.unpaired_surrogate()))
+            })
+            .collect()
+    }
+}
+
 impl Display for PartitionName {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let name = &self.0;
-        let name =
-            unsafe { String::from_raw_parts(name as *const u8 as *mut u8, name.len(), name.len()) };
-        write!(f, "{}", name)
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PartitionName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string_lossy())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PartitionName {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <String as serde::Deserialize>::deserialize(deserializer)?;
+        PartitionName::new(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn to_mbr_record_covers_the_partition() {
+        let part = testing::part(part_types::EFI_SYSTEM, 1, 2048, 4095);
+        let record = part.to_mbr_record(0xEF, true).unwrap();
+        assert_eq!((record.lb_start, record.lb_size), (2048, 2048));
+        assert_eq!((record.os_type, record.boot_indicator), (0xEF, 0x80));
+    }
+
+    #[test]
+    fn to_mbr_record_past_2_tib_is_none() {
+        let two_tib = 1u64 << 32;
+        let ends_past = testing::part(part_types::LINUX_FILESYSTEM, 1, 0xFFFF_0000, two_tib);
+        assert!(ends_past.to_mbr_record(0x83, false).is_none());
+        let starts_past = testing::part(part_types::LINUX_FILESYSTEM, 1, two_tib, two_tib + 10);
+        assert!(starts_past.to_mbr_record(0x83, false).is_none());
+        // the whole 32-bit range is one LBA too many for lb_size
+        let whole = testing::part(part_types::LINUX_FILESYSTEM, 1, 0, two_tib - 1);
+        assert!(whole.to_mbr_record(0x83, false).is_none());
+        let last = testing::part(part_types::LINUX_FILESYSTEM, 1, 0xFFFF_0000, two_tib - 1);
+        assert_eq!(last.to_mbr_record(0x83, false).unwrap().lb_size, 0x1_0000);
+    }
+
+    #[test]
+    fn alignment_offset_from_the_previous_multiple() {
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 2049, 4095);
+        assert_eq!(part.alignment_offset(2048), 1);
+        assert_eq!(part.alignment_offset(8), 1);
+        assert_eq!(part.alignment_offset(1), 0);
+        assert_eq!(part.alignment_offset(0), 0);
+    }
+
+    #[test]
+    fn is_aligned_to_multiples_of_the_alignment() {
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 4096, 6143);
+        assert!(part.is_aligned(2048));
+        assert!(part.is_aligned(4096));
+        assert!(!part.is_aligned(8192));
+        assert!(part.is_aligned(0));
+        let odd = testing::part(part_types::LINUX_FILESYSTEM, 1, 2049, 4095);
+        assert!(!odd.is_aligned(2048));
+        assert!(odd.is_aligned(1));
+    }
+
+    #[test]
+    fn size_of_a_single_sector_partition() {
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 2048, 2048);
+        assert_eq!(part.size_lba(), 1);
+        assert_eq!(part.byte_size(512), 512);
+        let huge = testing::part(part_types::LINUX_FILESYSTEM, 1, 0, u64::MAX - 1);
+        assert_eq!(huge.byte_size(4096), u64::MAX);
+    }
+
+    #[test]
+    fn byte_range_covers_the_inclusive_end() {
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 100, 109);
+        assert_eq!(part.byte_range(512), 51200..56320);
+        let last = testing::part(part_types::LINUX_FILESYSTEM, 1, u64::MAX - 1, u64::MAX - 1);
+        assert_eq!(last.byte_range(512), u64::MAX..u64::MAX);
+    }
+
+    #[test]
+    fn name_round_trips_a_surrogate_pair() {
+        let name = PartitionName::new("boot 😀").unwrap();
+        assert_eq!(name.char_len(), 7);
+        assert_eq!(&name.0[10..14], &[0x3D, 0xD8, 0x00, 0xDE]);
+        assert_eq!(name.to_string_lossy(), "boot 😀");
+        assert_eq!(name.try_to_string().unwrap(), "boot 😀");
+        // 18 emoji fill the 36 code units, a pair doesn't fit in the last unit
+        assert!(PartitionName::new(&"😀".repeat(18)).is_ok());
+        assert!(PartitionName::new(&"😀".repeat(19)).is_err());
+        assert!(PartitionName::new(&("a".repeat(35) + "😀")).is_err());
+    }
+
+    #[test]
+    fn reserved_bytes_of_a_256_byte_entry_round_trip() {
+        let mut entry =
+            testing::part(part_types::LINUX_FILESYSTEM, 1, 2048, 4095).serialize_entry(256);
+        entry[200] = 7;
+        entry[255] = 9;
+        let part = Partition::deserialize(&entry).unwrap().unwrap();
+        assert_eq!(part.reserved.len(), 128);
+        assert_eq!((part.reserved[72], part.reserved[127]), (7, 9));
+        assert_eq!(part.serialize_entry(256), entry);
+    }
+
+    #[test]
+    fn describe_and_display_show_the_type_name() {
+        let mut part = testing::part(part_types::EFI_SYSTEM, 1, 2048, 4095);
+        part.name = PartitionName::new("EFI").unwrap();
+        assert_eq!(
+            part.describe(),
+            "EFI System (c12a7328-f81f-11d2-ba4b-00a0c93ec93b) \"EFI\" 2048 sectors"
+        );
+        assert_eq!(
+            part.to_string(),
+            "Partition Entry {\n    \
+             partition type guid: EFI System (c12a7328-f81f-11d2-ba4b-00a0c93ec93b)\n    \
+             partition guid: 01010101-0101-0101-0101-010101010101\n    \
+             start_lba: 2048\n    \
+             end_lba: 4095\n    \
+             size: 2048 sectors\n    \
+             name: EFI\n\
+             }"
+        );
+    }
+
+    #[test]
+    fn equals_text_ignores_what_follows_the_null() {
+        let name = PartitionName::new("root").unwrap();
+        let mut padded = name.0;
+        // a stray byte past the terminating null
+        padded[20] = 0x41;
+        let padded = PartitionName::from(&padded[..]);
+        assert_ne!(name, padded);
+        assert!(name.equals_text(&padded));
+        assert!(!name.equals_text(&PartitionName::new("roo").unwrap()));
+    }
+
+    #[test]
+    fn cros_fields_set_and_read_back() {
+        let mut part = Partition {
+            attrs: 1,
+            ..Partition::default()
+        };
+        part.set_cros_priority(15);
+        part.set_cros_tries(3);
+        part.set_cros_successful(true);
+        assert_eq!(
+            (
+                part.cros_priority(),
+                part.cros_tries(),
+                part.cros_successful()
+            ),
+            (15, 3, true)
+        );
+        assert_eq!(part.attrs, 1 | 15 << 48 | 3 << 52 | 1 << 56);
+        // only the low 4 bits are kept, the neighbouring field is untouched
+        part.set_cros_priority(0x12);
+        assert_eq!((part.cros_priority(), part.cros_tries()), (2, 3));
+        part.set_cros_successful(false);
+        assert!(!part.cros_successful());
+        assert_eq!(part.attrs, 1 | 2 << 48 | 3 << 52);
+    }
+
+    #[test]
+    fn windows_flags_leave_the_low_bits_alone() {
+        let mut part = Partition {
+            attrs: 0b101,
+            ..Partition::default()
+        };
+        part.set_hidden(true);
+        assert!(part.is_hidden());
+        assert_eq!(part.attrs, 0b101 | 1 << 62);
+        part.set_read_only(true);
+        part.set_no_automount(true);
+        assert!(part.is_read_only() && part.is_no_automount() && !part.is_shadow_copy());
+        part.set_hidden(false);
+        assert!(!part.is_hidden());
+        assert_eq!(part.attrs, 0b101 | 1 << 60 | 1 << 63);
+    }
+
+    #[test]
+    fn attributes_name_the_flag_bits() {
+        let mut attributes = PartitionAttributes::from(0b101 | 0xBEEF << 48);
+        assert!(attributes.required());
+        assert!(!attributes.no_block_io());
+        assert!(attributes.legacy_bios_bootable());
+        assert_eq!(attributes.type_specific(), 0xBEEF);
+        attributes.set_no_block_io(true);
+        attributes.set_required(false);
+        attributes.set_type_specific(0x1234);
+        assert_eq!(u64::from(attributes), 0b110 | 0x1234 << 48);
+        assert_eq!(
+            alloc::format!("{attributes:?}"),
+            "PartitionAttributes { required: false, no_block_io: true, \
+             legacy_bios_bootable: true, type_specific: 0x1234 }"
+        );
+        let mut part = Partition::default();
+        part.set_attributes(attributes);
+        assert_eq!(part.attributes(), attributes);
+        assert_eq!(part.attrs, 0b110 | 0x1234 << 48);
+    }
+
+    #[test]
+    fn format_size_across_unit_boundaries() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1024 * 1024 - 1), "1023.9 KiB");
+        assert_eq!(format_size(1024 * 1024), "1.0 MiB");
+        assert_eq!(format_size(3 << 29), "1.5 GiB");
+        assert_eq!(format_size(u64::MAX), "15.9 EiB");
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 2048, 4095);
+        assert_eq!(part.size_string(512), "1.0 MiB");
+    }
+
+    #[test]
+    fn serialize_entry_zeroes_the_tail() {
+        let part = testing::part(part_types::LINUX_FILESYSTEM, 1, 2048, 4095);
+        let entry = part.serialize_entry(256);
+        assert_eq!(entry.len(), 256);
+        assert_eq!(entry[..PARTITION_LBA_SIZE], part.serialize());
+        assert!(entry[PARTITION_LBA_SIZE..].iter().all(|b| *b == 0));
+        // reserved bytes that don't fit are cut, the ones that do are kept
+        let vendor = Partition {
+            reserved: vec![7; 200],
+            ..part
+        };
+        let entry = vendor.serialize_entry(256);
+        assert!(entry[PARTITION_LBA_SIZE..].iter().all(|b| *b == 7));
+    }
+
+    #[test]
+    fn unpaired_surrogate_is_replaced_or_rejected() {
+        let mut raw = [0u8; 72];
+        raw[..2].copy_from_slice(&0x0041u16.to_le_bytes());
+        // a high surrogate followed by a plain code unit
+        raw[2..4].copy_from_slice(&0xD83Du16.to_le_bytes());
+        raw[4..6].copy_from_slice(&0x0042u16.to_le_bytes());
+        let name = PartitionName::from(&raw[..]);
+        assert_eq!(name.to_string_lossy(), "A\u{FFFD}B");
+        assert!(matches!(
+            name.try_to_string(),
+            Err(PartitionNameError::UnpairedSurrogate(0xD83D))
+        ));
+    }
+
+    #[test]
+    fn short_entry_size_is_raised_to_the_standard_fields() {
+        let part = testing::layout().partition(1).unwrap().clone();
+        let entry = part.serialize_entry(64);
+        assert_eq!(entry.len(), PARTITION_LBA_SIZE);
+        assert_eq!(entry[..], part.serialize()[..]);
+    }
+
+    #[test]
+    fn clear_resets_to_an_unused_entry() {
+        let mut part = testing::layout().partition(1).unwrap().clone();
+        part.clear();
+        assert_eq!(part, Partition::default());
+        assert!(Partition::deserialize(&part.serialize()).unwrap().is_none());
+        assert_eq!(part.serialize(), [0u8; PARTITION_LBA_SIZE]);
     }
 }
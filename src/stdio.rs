@@ -0,0 +1,172 @@
+//! Reading and writing a layout through `std::io`, for files and block devices on a hosted
+//! platform.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use super::{
+    io::{check_block_size, BlockIo, BlockRead},
+    BlockError, GptError, GptLayout,
+};
+
+/// Anything seekable, addressed in blocks of `block_size` bytes.
+struct StdDisk<'a, T> {
+    inner: &'a mut T,
+    block_size: usize,
+    block_count: u64,
+}
+
+impl<'a, T: Seek> StdDisk<'a, T> {
+    fn new(inner: &'a mut T, block_size: usize) -> Result<Self, BlockError> {
+        check_block_size(block_size)?;
+        let len = inner.seek(SeekFrom::End(0)).map_err(block_error)?;
+        Ok(Self {
+            inner,
+            block_size,
+            block_count: len / block_size as u64,
+        })
+    }
+
+    fn seek_to(&mut self, lba: u64) -> Result<(), BlockError> {
+        self.inner
+            .seek(SeekFrom::Start(lba * self.block_size as u64))
+            .map_err(block_error)?;
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek> BlockRead for StdDisk<'_, T> {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        self.block_count
+    }
+
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.seek_to(lba)?;
+        self.inner.read_exact(buf).map_err(block_error)
+    }
+}
+
+impl<T: Read + Write + Seek> BlockIo for StdDisk<'_, T> {
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        self.seek_to(lba)?;
+        self.inner.write_all(buf).map_err(block_error)
+    }
+}
+
+/// A `std::io::Read` stream seen through the crate's own [`Read`](super::io::Read).
+struct StdStream<'a, T>(&'a mut T);
+
+impl<T: Read> super::io::Read for StdStream<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, BlockError> {
+        loop {
+            match self.0.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                read => return read.map_err(block_error),
+            }
+        }
+    }
+}
+
+fn block_error(err: io::Error) -> BlockError {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => BlockError::UnexpectedEof,
+        _ => BlockError::Device,
+    }
+}
+
+impl GptLayout {
+    /// Parse the layout of a file or device, `block_size` being the LBA size in bytes, with
+    /// the same checks as [`GptLayout::load`]. The backup header is looked up where the
+    /// primary header says it is.
+    pub fn read_from<R: Read + Seek>(reader: &mut R, block_size: usize) -> Result<Self, GptError> {
+        Self::load(&mut StdDisk::new(reader, block_size)?)
+    }
+
+    /// Parse a disk image from a `std::io` source which can't seek, like stdin or a pipe,
+    /// see [`GptLayout::read_from_stream`].
+    pub fn read_from_std_stream<R: Read>(
+        reader: &mut R,
+        sector_size: usize,
+        disk_sectors: u64,
+    ) -> Result<Self, GptError> {
+        Self::read_from_stream(&mut StdStream(reader), sector_size, disk_sectors)
+    }
+
+    /// Write the layout to a file or device like [`GptLayout::store`], failing with
+    /// [`LayoutError::DiskTooSmall`](super::LayoutError::DiskTooSmall) before anything is
+    /// written if it doesn't fit in the current length of `writer`. The CRCs are written as
+    /// they are, see [`GptLayout::recompute_crc32`].
+    pub fn write_to<W: Read + Write + Seek>(
+        &self,
+        writer: &mut W,
+        block_size: usize,
+    ) -> Result<(), GptError> {
+        self.store(&mut StdDisk::new(writer, block_size)?)?;
+        writer.flush().map_err(block_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Read},
+        vec,
+    };
+
+    use crate::{testing, BlockError, GptError, GptLayout, LayoutError};
+
+    #[test]
+    fn read_from_rejects_corrupt_crc() {
+        let mut disk = testing::disk();
+        assert_eq!(
+            GptLayout::read_from(&mut Cursor::new(&disk), 512).unwrap(),
+            testing::layout()
+        );
+        // an entry of the primary array
+        disk[1024 + 40] ^= 1;
+        assert!(GptLayout::read_from(&mut Cursor::new(&disk), 512).is_err());
+    }
+
+    #[test]
+    fn read_from_std_stream_reads_a_pipe() {
+        let disk = testing::disk();
+        let mut pipe = Cursor::new(&disk).chain(std::io::empty());
+        assert_eq!(
+            GptLayout::read_from_std_stream(&mut pipe, 512, testing::DISK_LBAS).unwrap(),
+            testing::layout()
+        );
+        let mut short = &disk[..disk.len() - 1];
+        assert!(GptLayout::read_from_std_stream(&mut short, 512, testing::DISK_LBAS).is_err());
+    }
+
+    #[test]
+    fn write_to_checks_the_layout_fits() {
+        let mut small = Cursor::new(vec![0u8; 1024]);
+        assert!(matches!(
+            testing::layout().write_to(&mut small, 512),
+            Err(GptError::Layout(LayoutError::DiskTooSmall))
+        ));
+        assert_eq!(small.get_ref(), &[0u8; 1024]);
+
+        let mut disk = Cursor::new(vec![0u8; testing::DISK_LBAS as usize * 512]);
+        testing::layout().write_to(&mut disk, 512).unwrap();
+        assert_eq!(disk.get_ref(), &testing::disk());
+    }
+
+    #[test]
+    fn zero_block_size_is_rejected() {
+        let mut disk = Cursor::new(testing::disk());
+        assert!(matches!(
+            GptLayout::read_from(&mut disk, 0),
+            Err(GptError::Block(BlockError::InvalidBlockSize))
+        ));
+        assert!(matches!(
+            testing::layout().write_to(&mut disk, 0),
+            Err(GptError::Block(BlockError::InvalidBlockSize))
+        ));
+    }
+}
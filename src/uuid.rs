@@ -1,12 +1,120 @@
-use core::{fmt::Display, ops::Deref};
+use core::{
+    fmt::{Display, LowerHex, UpperHex},
+    ops::Deref,
+};
 
-#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+use alloc::{format, string::String};
+
+use super::UuidError;
+
+/// Ordered byte-wise on the on-disk (mixed-endian) representation.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Uuid([u8; 16]);
 
 impl Uuid {
+    /// The all-zero GUID, marks unused partition entries.
+    pub const NIL: Uuid = Uuid([0u8; 16]);
+
+    /// The GUID with the 16 bytes as stored on disk, the first three fields little endian.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// The 16 bytes as stored on disk, what [`Uuid::from_bytes`] takes.
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// The GUID written `d1-d2-d3-d4[0..2]-d4[2..8]`, the first three fields stored little
+    /// endian as on disk.
+    pub(crate) const fn from_fields(d1: u32, d2: u16, d3: u16, d4: [u8; 8]) -> Self {
+        let d1 = d1.to_le_bytes();
+        let d2 = d2.to_le_bytes();
+        let d3 = d3.to_le_bytes();
+        Self([
+            d1[0], d1[1], d1[2], d1[3], d2[0], d2[1], d3[0], d3[1], d4[0], d4[1], d4[2], d4[3],
+            d4[4], d4[5], d4[6], d4[7],
+        ])
+    }
+
+    /// The GUID whose text form is the 32 hex digits of `value`, i.e. `value` is the
+    /// logical big-endian number shown by `Display`, not the on-disk byte layout.
+    pub const fn from_u128(value: u128) -> Self {
+        let b = value.to_be_bytes();
+        Self([
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9], b[10], b[11], b[12], b[13],
+            b[14], b[15],
+        ])
+    }
+
+    /// The logical big-endian value of the GUID, the number its text form spells out, see
+    /// [`Uuid::from_u128`].
+    pub const fn as_u128(&self) -> u128 {
+        let b = self.0;
+        u128::from_be_bytes([
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9], b[10], b[11], b[12], b[13],
+            b[14], b[15],
+        ])
+    }
+
     pub fn validate(&self) -> bool {
         self.0 != [0u8; 16]
     }
+
+    /// Whether this is the all-zero GUID.
+    pub fn is_nil(&self) -> bool {
+        !self.validate()
+    }
+
+    /// A random (version 4) GUID built from two outputs of `rng`, the crate has no source
+    /// of randomness of its own.
+    pub fn new_v4(rng: &mut impl FnMut() -> u64) -> Self {
+        let mut uuid = [0u8; 16];
+        uuid[0..8].copy_from_slice(&rng().to_le_bytes());
+        uuid[8..16].copy_from_slice(&rng().to_le_bytes());
+        // the third field is stored little endian, its high nibble (the version) is byte 7
+        uuid[7] = (uuid[7] & 0x0F) | 0x40;
+        // the variant lives in the big endian fourth field
+        uuid[8] = (uuid[8] & 0x3F) | 0x80;
+        Self(uuid)
+    }
+
+    /// Parse the canonical `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` form that `Display`
+    /// produces, in either case and optionally wrapped in braces. The first three fields are
+    /// stored little endian.
+    pub fn parse_str(s: &str) -> Result<Self, UuidError> {
+        let s = s
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s)
+            .as_bytes();
+        if s.len() != 36 || [8, 13, 18, 23].iter().any(|hyphen| s[*hyphen] != b'-') {
+            return Err(UuidError::InvalidLength);
+        }
+        let mut digits = s.iter().filter(|c| **c != b'-');
+        let mut fields = [0u8; 16];
+        for byte in fields.iter_mut() {
+            let high = hex_digit(*digits.next().ok_or(UuidError::InvalidLength)?)?;
+            let low = hex_digit(*digits.next().ok_or(UuidError::InvalidLength)?)?;
+            *byte = high << 4 | low;
+        }
+        if digits.next().is_some() {
+            return Err(UuidError::InvalidLength);
+        }
+        fields[0..4].reverse();
+        fields[4..6].reverse();
+        fields[6..8].reverse();
+        Ok(Self(fields))
+    }
+}
+
+fn hex_digit(c: u8) -> Result<u8, UuidError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(UuidError::InvalidCharacter),
+    }
 }
 
 impl Deref for Uuid {
@@ -17,6 +125,13 @@ impl Deref for Uuid {
     }
 }
 
+/// Same as [`Uuid::from_bytes`], the bytes in on-disk order.
+impl From<[u8; 16]> for Uuid {
+    fn from(value: [u8; 16]) -> Self {
+        Self(value)
+    }
+}
+
 impl From<&[u8]> for Uuid {
     fn from(value: &[u8]) -> Self {
         assert_eq!(value.len(), 16);
@@ -26,27 +141,138 @@ impl From<&[u8]> for Uuid {
     }
 }
 
+impl Uuid {
+    /// The Windows registry form, uppercase in braces like
+    /// `{C12A7328-F81F-11D2-BA4B-00A0C93EC93B}`, which [`Uuid::parse_str`] accepts too.
+    pub fn to_braced(&self) -> String {
+        format!("{{{self:X}}}")
+    }
+
+    /// Write the canonical form, the first three fields byte swapped back to big endian.
+    fn write_hex(&self, f: &mut core::fmt::Formatter<'_>, upper: bool) -> core::fmt::Result {
+        const ORDER: [usize; 16] = [3, 2, 1, 0, 5, 4, 7, 6, 8, 9, 10, 11, 12, 13, 14, 15];
+        for (position, index) in ORDER.iter().enumerate() {
+            if [4, 6, 8, 10].contains(&position) {
+                write!(f, "-")?;
+            }
+            if upper {
+                write!(f, "{:02X}", self.0[*index])?;
+            } else {
+                write!(f, "{:02x}", self.0[*index])?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Display for Uuid {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(
-            f,
-            "{:x}{:x}{:x}{:x}-{:x}{:x}-{:x}{:x}-{:x}{:x}-{:x}{:x}{:x}{:x}{:x}{:x}",
-            self.0[3],
-            self.0[2],
-            self.0[1],
-            self.0[0],
-            self.0[5],
-            self.0[4],
-            self.0[7],
-            self.0[6],
-            self.0[8],
-            self.0[9],
-            self.0[10],
-            self.0[11],
-            self.0[12],
-            self.0[13],
-            self.0[14],
-            self.0[15]
-        )
+        self.write_hex(f, false)
+    }
+}
+
+/// Same as `Display`.
+impl LowerHex for Uuid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_hex(f, false)
+    }
+}
+
+/// The canonical form in uppercase, as `gdisk` prints it.
+impl UpperHex for Uuid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_hex(f, true)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Uuid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Uuid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UuidVisitor;
+
+        impl serde::de::Visitor<'_> for UuidVisitor {
+            type Value = Uuid;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "a GUID string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Uuid, E> {
+                Uuid::parse_str(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(UuidVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeSet, vec};
+
+    use super::*;
+
+    #[test]
+    fn sorts_byte_wise() {
+        let mut guids = vec![
+            Uuid::from_bytes([2; 16]),
+            Uuid::NIL,
+            Uuid::from_bytes([1; 16]),
+            Uuid::from_bytes([1; 16]),
+        ];
+        guids.sort();
+        assert_eq!(
+            guids,
+            [
+                Uuid::NIL,
+                Uuid::from_bytes([1; 16]),
+                Uuid::from_bytes([1; 16]),
+                Uuid::from_bytes([2; 16])
+            ]
+        );
+        let set: BTreeSet<Uuid> = guids.into_iter().collect();
+        assert_eq!(set.len(), 3);
+        // the first on-disk byte decides, not the text form
+        let mut high_last_byte = [0u8; 16];
+        high_last_byte[15] = 0xFF;
+        let mut low_first_byte = [0u8; 16];
+        low_first_byte[0] = 1;
+        assert!(Uuid::from_bytes(high_last_byte) < Uuid::from_bytes(low_first_byte));
+    }
+
+    #[test]
+    fn braced_form_round_trips() {
+        let braced = "{C12A7328-F81F-11D2-BA4B-00A0C93EC93B}";
+        let guid = Uuid::parse_str(braced).unwrap();
+        assert_eq!(guid, crate::part_types::EFI_SYSTEM);
+        assert_eq!(guid.to_braced(), braced);
+        assert!(Uuid::parse_str("{C12A7328-F81F-11D2-BA4B-00A0C93EC93B").is_err());
+        assert!(Uuid::parse_str("C12A7328-F81F-11D2-BA4B-00A0C93EC93B}").is_err());
+    }
+
+    #[test]
+    fn hex_formatting_follows_the_case_flag() {
+        let guid = crate::part_types::EFI_SYSTEM;
+        assert_eq!(format!("{guid:X}"), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(format!("{guid:x}"), "c12a7328-f81f-11d2-ba4b-00a0c93ec93b");
+        assert_eq!(format!("{guid}"), format!("{guid:x}"));
+    }
+
+    #[test]
+    fn u128_is_the_value_display_shows() {
+        let guid = crate::part_types::EFI_SYSTEM;
+        let value = guid.as_u128();
+        assert_eq!(value, 0xC12A7328_F81F_11D2_BA4B_00A0C93EC93B);
+        assert_eq!(Uuid::from_u128(value), guid);
+        assert_eq!(Uuid::from_u128(0), Uuid::NIL);
+        // the first field is little endian on disk
+        assert_eq!(guid.as_bytes()[..4], [0x28, 0x73, 0x2A, 0xC1]);
     }
 }